@@ -3,7 +3,7 @@
 //! The types defined here get used throughout iamb.
 use std::borrow::Cow;
 use std::collections::hash_map::IntoIter;
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::convert::TryFrom;
 use std::fmt::{self, Display};
 use std::hash::Hash;
@@ -15,6 +15,7 @@ use emojis::Emoji;
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Rect},
+    style::{Modifier as StyleModifier, Style},
     text::{Line, Span},
     widgets::{Paragraph, Widget},
 };
@@ -34,10 +35,16 @@ use matrix_sdk::{
     encryption::verification::SasVerification,
     room::Room as MatrixRoom,
     ruma::{
+        api::client::{device::Device as MatrixDevice, space::SpaceHierarchyRoomsChunk},
+        directory::PublicRoomsChunk,
         events::{
+            poll::{
+                start::{PollContentBlock, PollKind},
+                unstable_start::{UnstablePollStartContentBlock, UnstablePollStartEventContent},
+            },
             reaction::ReactionEvent,
             relation::{Replacement, Thread},
-            room::encrypted::RoomEncryptedEvent,
+            room::encrypted::{OriginalRoomEncryptedEvent, RoomEncryptedEvent},
             room::message::{
                 OriginalRoomMessageEvent,
                 Relation,
@@ -46,13 +53,20 @@ use matrix_sdk::{
                 RoomMessageEventContentWithoutRelation,
             },
             room::redaction::{OriginalSyncRoomRedactionEvent, SyncRoomRedactionEvent},
+            room::tombstone::RoomTombstoneEventContent,
             tag::{TagName, Tags},
+            AnyMessageLikeEvent,
             MessageLikeEvent,
+            MessageLikeUnsigned,
+            TimelineEventType,
         },
         presence::PresenceState,
         EventId,
+        MilliSecondsSinceUnixEpoch,
+        OwnedDeviceId,
         OwnedEventId,
         OwnedRoomId,
+        OwnedServerName,
         OwnedUserId,
         RoomId,
         RoomVersionId,
@@ -85,14 +99,15 @@ use modalkit::{
     errors::{UIError, UIResult},
     key::TerminalKey,
     keybindings::SequenceStatus,
-    prelude::{CommandType, WordStyle},
+    prelude::{CommandType, InfoMessage, WordStyle},
 };
 
 use crate::config::ImagePreviewProtocolValues;
 use crate::message::ImageStatus;
 use crate::preview::{source_from_event, spawn_insert_preview};
+use crate::search::MessageIndex;
 use crate::{
-    message::{Message, MessageEvent, MessageKey, MessageTimeStamp, Messages},
+    message::{Message, MessageEncryption, MessageEvent, MessageKey, MessageTimeStamp, Messages},
     worker::Requester,
     ApplicationSettings,
 };
@@ -151,6 +166,21 @@ pub enum MessageAction {
     /// Edit a sent message.
     Edit,
 
+    /// Show how a message was encrypted, and whether its sender can be trusted.
+    Encryption,
+
+    /// Download the room key for a message that couldn't be decrypted from the key backup.
+    DownloadBackupKey,
+
+    /// Pin a message to the room.
+    Pin,
+
+    /// End a poll that the user started.
+    PollEnd,
+
+    /// Vote for an answer in a poll, numbered from 1.
+    PollVote(usize),
+
     /// React to a message with an Emoji.
     ///
     /// `:react` will by default try to convert the [String] argument to an Emoji, and error when
@@ -166,6 +196,9 @@ pub enum MessageAction {
     /// Reply to a message.
     Reply,
 
+    /// Show the JSON source of a message.
+    Source,
+
     /// Unreact to a message.
     ///
     /// If no specific Emoji to remove to is specified, then all reactions from the user on the
@@ -175,6 +208,9 @@ pub enum MessageAction {
     /// and error when it doesn't recognize it. The second [bool] argument forces it to be
     /// interpreted literally when it is `true`.
     Unreact(Option<String>, bool),
+
+    /// Unpin a message from the room.
+    Unpin,
 }
 
 /// The type of room being created.
@@ -205,6 +241,21 @@ bitflags::bitflags! {
     }
 }
 
+bitflags::bitflags! {
+    /// Available options when searching for messages.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct SearchFlags: u32 {
+        /// No flags specified.
+        const NONE = 0b00000000;
+
+        /// Search all joined rooms instead of only the current one.
+        const ALL = 0b00000001;
+
+        /// Search the local message index instead of asking the homeserver.
+        const LOCAL = 0b00000010;
+    }
+}
+
 bitflags::bitflags! {
     /// Available options when downloading files.
     #[derive(Clone, Debug, Eq, PartialEq)]
@@ -372,6 +423,9 @@ pub enum RoomField {
     /// The room's history visibility.
     History,
 
+    /// The room's join rule.
+    JoinRule,
+
     /// The room name.
     Name,
 
@@ -392,6 +446,21 @@ pub enum RoomField {
 
     /// The room's canonical alias.
     CanonicalAlias,
+
+    /// The room's entire list of power levels.
+    PowerLevels,
+
+    /// The power level of a specific user.
+    PowerLevel(OwnedUserId),
+
+    /// The power level needed to send a specific type of event.
+    EventPowerLevel(TimelineEventType),
+
+    /// The servers allowed to participate in the room.
+    ServerAcl,
+
+    /// The user's display name in this room.
+    Nick,
 }
 
 /// An action that operates on a room member.
@@ -400,6 +469,29 @@ pub enum MemberUpdateAction {
     Ban,
     Kick,
     Unban,
+
+    /// Let in a user who has knocked on the room.
+    KnockAccept,
+
+    /// Turn away a user who has knocked on the room.
+    KnockDeny,
+}
+
+/// How far back to look for messages to redact when purging a user's messages.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PurgeLimit {
+    /// Redact up to this many of the user's most recent messages.
+    Count(usize),
+
+    /// Redact the user's messages sent within this long of now.
+    Since(Duration),
+}
+
+/// An action that changes which servers may participate in a room.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ServerAclAction {
+    Allow,
+    Deny,
 }
 
 impl Display for MemberUpdateAction {
@@ -408,6 +500,8 @@ impl Display for MemberUpdateAction {
             MemberUpdateAction::Ban => write!(f, "ban"),
             MemberUpdateAction::Kick => write!(f, "kick"),
             MemberUpdateAction::Unban => write!(f, "unban"),
+            MemberUpdateAction::KnockAccept => write!(f, "accept the knock from"),
+            MemberUpdateAction::KnockDeny => write!(f, "deny the knock from"),
         }
     }
 }
@@ -415,15 +509,24 @@ impl Display for MemberUpdateAction {
 /// An action that operates on a focused room.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum RoomAction {
+    /// Show or edit the JSON content of this room's account data.
+    AccountData(RawJsonAction),
+
     /// Accept an invitation to join this room.
     InviteAccept,
 
+    /// Enable end-to-end encryption in this room.
+    EnableEncryption(bool),
+
     /// Reject an invitation to join this room.
     InviteReject,
 
     /// Invite a user to this room.
     InviteSend(OwnedUserId),
 
+    /// Join the room that replaced this one after it was upgraded.
+    JoinSuccessor,
+
     /// Leave this room.
     Leave(bool),
 
@@ -433,6 +536,30 @@ pub enum RoomAction {
     /// Open the members window.
     Members(Box<CommandContext>),
 
+    /// Open the pinned messages window.
+    Pins(Box<CommandContext>),
+
+    /// Redact a user's recent messages in this room.
+    Purge(String, PurgeLimit, Option<String>, bool),
+
+    /// Allow or deny servers matching a pattern in this room's server ACL.
+    ServerAcl(ServerAclAction, String, bool),
+
+    /// Add a room as a child of this space.
+    SpaceChildAdd(String),
+
+    /// Remove a child room from this space.
+    SpaceChildRemove(String),
+
+    /// Set a space as this room's parent.
+    SpaceParentSet(String),
+
+    /// Show the room's state events, optionally filtered by type and state key.
+    StateShow(Option<String>, Option<String>),
+
+    /// Edit the JSON content of the state event with the given type and state key.
+    StateEdit(String, String),
+
     /// Set whether a room is a direct message.
     SetDirect(bool),
 
@@ -460,16 +587,111 @@ pub enum SendAction {
 
     /// Upload the image data.
     UploadImage(usize, usize, Cow<'static, [u8]>),
+
+    /// Start a poll with a question and its possible answers.
+    Poll(String, Vec<String>),
 }
 
 /// An action performed against the user's homeserver.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum HomeserverAction {
-    /// Create a new room with an optional localpart.
-    CreateRoom(Option<String>, CreateRoomType, CreateRoomFlags),
+    /// Show or edit the JSON content of the user's global account data.
+    AccountData(RawJsonAction),
+
+    /// Create a new room with an optional localpart, and an optional parent space.
+    CreateRoom(Option<String>, CreateRoomType, CreateRoomFlags, Option<String>),
+
+    /// Join a room through the given servers, and then switch to it.
+    JoinRoom(OwnedRoomId, Vec<OwnedServerName>),
+
+    /// Hide messages, reactions and typing notices from a user.
+    Ignore(String),
+
+    /// Set our presence, with an optional status message.
+    Presence(PresenceState, Option<String>),
+
+    /// Ask to join a room, with an optional reason.
+    Knock(String, Option<String>),
+
+    /// Show the users who are currently being ignored.
+    ListIgnored,
+
+    /// Stop ignoring a user.
+    Unignore(String),
     Logout(String, bool),
 }
 
+/// An action that shows or edits the raw JSON content of an account data event.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RawJsonAction {
+    /// Show the content for the given event type.
+    Show(String),
+
+    /// Edit the content for the given event type in the external editor.
+    Edit(String),
+}
+
+/// An action that changes the user's own profile.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ProfileAction {
+    /// Set the user's global display name.
+    SetName(String),
+
+    /// Remove the user's global display name.
+    UnsetName,
+
+    /// Upload an image file, and use it as the user's avatar.
+    SetAvatar(String),
+}
+
+/// An action that changes which rooms are shown inside of a space's hierarchy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FoldAction {
+    /// Show the rooms inside the selected space.
+    Open,
+
+    /// Hide the rooms inside the selected space.
+    Close,
+
+    /// Toggle whether the rooms inside the selected space are shown.
+    Toggle,
+
+    /// Show the rooms inside every space.
+    OpenAll,
+
+    /// Hide the rooms inside every space.
+    CloseAll,
+}
+
+/// An action performed on the public room directory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DirectoryAction {
+    /// Search the public rooms of a server, or our homeserver when [None], with an optional
+    /// filter.
+    Search(Option<OwnedServerName>, Option<String>),
+
+    /// Fetch the next page of results for the current directory search.
+    NextPage,
+}
+
+/// An action performed on the user's own devices.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DevicesAction {
+    /// Fetch the user's devices, and then open the `:devices` window.
+    List,
+
+    /// Change the display name of a device.
+    Rename(OwnedDeviceId, String),
+
+    /// Delete a device, signing it out.
+    ///
+    /// The [bool] argument indicates whether to skip confirmation.
+    Delete(OwnedDeviceId, bool),
+
+    /// Request verification of a device.
+    Verify(OwnedDeviceId),
+}
+
 /// An action performed against the user's room keys.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum KeysAction {
@@ -477,6 +699,29 @@ pub enum KeysAction {
     Export(String, String),
     /// Import room keys from a file, encrypted with a passphrase.
     Import(String, String),
+
+    /// Set up server-side key backup and recovery, optionally protected by a passphrase.
+    BackupEnable(Option<String>),
+
+    /// Show the state of server-side key backup and recovery.
+    BackupStatus,
+
+    /// Restore secrets and room keys using a recovery key or passphrase.
+    BackupRestore(String),
+
+    /// Retry decrypting the loaded messages in a room, optionally only those encrypted with a
+    /// specific Megolm session.
+    Retry(OwnedRoomId, Option<String>),
+}
+
+/// An action performed against a user's cryptographic identity.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum IdentityAction {
+    /// Show a user's cross-signing status and devices.
+    Show(OwnedUserId),
+
+    /// Stop treating a user's identity as verified, acknowledging that it has changed.
+    Withdraw(OwnedUserId),
 }
 
 /// An action that the main program loop should.
@@ -484,9 +729,18 @@ pub enum KeysAction {
 /// See [the commands module][super::commands] for where these are usually created.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum IambAction {
+    /// Perform an action on the user's own devices.
+    Devices(DevicesAction),
+
+    /// Perform an action on the public room directory.
+    Directory(DirectoryAction),
+
     /// Perform an action against the homeserver.
     Homeserver(HomeserverAction),
 
+    /// Perform an action on a user's cryptographic identity.
+    Identity(IdentityAction),
+
     /// Perform an action over room keys.
     Keys(KeysAction),
 
@@ -496,9 +750,18 @@ pub enum IambAction {
     /// Open a URL.
     OpenLink(String),
 
+    /// Change the user's own profile.
+    Profile(ProfileAction),
+
     /// Perform an action on the currently focused room.
     Room(RoomAction),
 
+    /// Search for messages containing the given terms.
+    Search(String, SearchFlags),
+
+    /// Open a room's scrollback at the given message.
+    JumpToEvent(OwnedRoomId, OwnedEventId),
+
     /// Send a message to the currently focused room.
     Send(SendAction),
 
@@ -511,6 +774,9 @@ pub enum IambAction {
     /// Toggle the focus within the focused room.
     ToggleScrollbackFocus,
 
+    /// Fold or unfold spaces within the focused space's hierarchy.
+    Fold(FoldAction),
+
     /// Clear all unread messages.
     ClearUnreads,
 }
@@ -518,7 +784,32 @@ pub enum IambAction {
 impl IambAction {
     /// Indicates whether this action will draw over the screen.
     pub fn scribbles(&self) -> bool {
-        matches!(self, IambAction::Send(SendAction::SubmitFromEditor))
+        matches!(
+            self,
+            IambAction::Send(SendAction::SubmitFromEditor) |
+                IambAction::Devices(DevicesAction::Delete(_, true)) |
+                IambAction::Room(RoomAction::StateEdit(_, _)) |
+                IambAction::Room(RoomAction::AccountData(RawJsonAction::Edit(_))) |
+                IambAction::Homeserver(HomeserverAction::AccountData(RawJsonAction::Edit(_)))
+        )
+    }
+}
+
+impl From<DevicesAction> for IambAction {
+    fn from(act: DevicesAction) -> Self {
+        IambAction::Devices(act)
+    }
+}
+
+impl From<IdentityAction> for IambAction {
+    fn from(act: IdentityAction) -> Self {
+        IambAction::Identity(act)
+    }
+}
+
+impl From<DirectoryAction> for IambAction {
+    fn from(act: DirectoryAction) -> Self {
+        IambAction::Directory(act)
     }
 }
 
@@ -534,6 +825,12 @@ impl From<MessageAction> for IambAction {
     }
 }
 
+impl From<ProfileAction> for IambAction {
+    fn from(act: ProfileAction) -> Self {
+        IambAction::Profile(act)
+    }
+}
+
 impl From<RoomAction> for IambAction {
     fn from(act: RoomAction) -> Self {
         IambAction::Room(act)
@@ -550,11 +847,18 @@ impl ApplicationAction for IambAction {
     fn is_edit_sequence(&self, _: &EditContext) -> SequenceStatus {
         match self {
             IambAction::ClearUnreads => SequenceStatus::Break,
+            IambAction::Devices(..) => SequenceStatus::Break,
+            IambAction::Directory(..) => SequenceStatus::Break,
+            IambAction::Fold(..) => SequenceStatus::Break,
             IambAction::Homeserver(..) => SequenceStatus::Break,
+            IambAction::Identity(..) => SequenceStatus::Break,
             IambAction::Keys(..) => SequenceStatus::Break,
             IambAction::Message(..) => SequenceStatus::Break,
             IambAction::Room(..) => SequenceStatus::Break,
+            IambAction::Search(..) => SequenceStatus::Break,
+            IambAction::JumpToEvent(..) => SequenceStatus::Break,
             IambAction::OpenLink(..) => SequenceStatus::Break,
+            IambAction::Profile(..) => SequenceStatus::Break,
             IambAction::Send(..) => SequenceStatus::Break,
             IambAction::ToggleScrollbackFocus => SequenceStatus::Break,
             IambAction::Verify(..) => SequenceStatus::Break,
@@ -565,11 +869,18 @@ impl ApplicationAction for IambAction {
     fn is_last_action(&self, _: &EditContext) -> SequenceStatus {
         match self {
             IambAction::ClearUnreads => SequenceStatus::Atom,
+            IambAction::Devices(..) => SequenceStatus::Atom,
+            IambAction::Directory(..) => SequenceStatus::Atom,
+            IambAction::Fold(..) => SequenceStatus::Atom,
             IambAction::Homeserver(..) => SequenceStatus::Atom,
+            IambAction::Identity(..) => SequenceStatus::Atom,
             IambAction::Keys(..) => SequenceStatus::Atom,
             IambAction::Message(..) => SequenceStatus::Atom,
             IambAction::OpenLink(..) => SequenceStatus::Atom,
+            IambAction::Profile(..) => SequenceStatus::Atom,
             IambAction::Room(..) => SequenceStatus::Atom,
+            IambAction::Search(..) => SequenceStatus::Atom,
+            IambAction::JumpToEvent(..) => SequenceStatus::Atom,
             IambAction::Send(..) => SequenceStatus::Atom,
             IambAction::ToggleScrollbackFocus => SequenceStatus::Atom,
             IambAction::Verify(..) => SequenceStatus::Atom,
@@ -580,11 +891,18 @@ impl ApplicationAction for IambAction {
     fn is_last_selection(&self, _: &EditContext) -> SequenceStatus {
         match self {
             IambAction::ClearUnreads => SequenceStatus::Ignore,
+            IambAction::Devices(..) => SequenceStatus::Ignore,
+            IambAction::Directory(..) => SequenceStatus::Ignore,
+            IambAction::Fold(..) => SequenceStatus::Ignore,
             IambAction::Homeserver(..) => SequenceStatus::Ignore,
+            IambAction::Identity(..) => SequenceStatus::Ignore,
             IambAction::Keys(..) => SequenceStatus::Ignore,
             IambAction::Message(..) => SequenceStatus::Ignore,
             IambAction::Room(..) => SequenceStatus::Ignore,
+            IambAction::Search(..) => SequenceStatus::Ignore,
+            IambAction::JumpToEvent(..) => SequenceStatus::Ignore,
             IambAction::OpenLink(..) => SequenceStatus::Ignore,
+            IambAction::Profile(..) => SequenceStatus::Ignore,
             IambAction::Send(..) => SequenceStatus::Ignore,
             IambAction::ToggleScrollbackFocus => SequenceStatus::Ignore,
             IambAction::Verify(..) => SequenceStatus::Ignore,
@@ -595,12 +913,19 @@ impl ApplicationAction for IambAction {
     fn is_switchable(&self, _: &EditContext) -> bool {
         match self {
             IambAction::ClearUnreads => false,
+            IambAction::Devices(..) => false,
+            IambAction::Directory(..) => false,
+            IambAction::Fold(..) => false,
             IambAction::Homeserver(..) => false,
+            IambAction::Identity(..) => false,
             IambAction::Message(..) => false,
             IambAction::Room(..) => false,
+            IambAction::Search(..) => false,
+            IambAction::JumpToEvent(..) => false,
             IambAction::Keys(..) => false,
             IambAction::Send(..) => false,
             IambAction::OpenLink(..) => false,
+            IambAction::Profile(..) => false,
             IambAction::ToggleScrollbackFocus => false,
             IambAction::Verify(..) => false,
             IambAction::VerifyRequest(..) => false,
@@ -650,6 +975,22 @@ pub enum IambError {
     #[error("Invalid history visibility setting: {0}")]
     InvalidHistoryVisibility(String),
 
+    /// An invalid join rule was specified.
+    #[error("Invalid join rule: {0}")]
+    InvalidJoinRule(String),
+
+    /// Edited JSON that couldn't be parsed into an object.
+    #[error("Invalid JSON object: {0}")]
+    InvalidJson(String),
+
+    /// An invalid server name pattern was specified.
+    #[error("Invalid server name pattern: {0}")]
+    InvalidServerAcl(String),
+
+    /// An invalid power level was specified.
+    #[error("Invalid power level: {0}")]
+    InvalidPowerLevel(String),
+
     /// An invalid notification level was specified.
     #[error("Invalid notification level: {0}")]
     InvalidNotificationLevel(String),
@@ -673,6 +1014,10 @@ pub enum IambError {
     #[error("Failed to import room keys: {0}")]
     FailedKeyImport(#[from] matrix_sdk::encryption::RoomKeyImportError),
 
+    /// A failure while setting up or using key backup and recovery.
+    #[error("Key backup and recovery error: {0}")]
+    Recovery(#[from] matrix_sdk::encryption::recovery::RecoveryError),
+
     /// A failure related to the cryptographic store.
     #[error("Cannot export keys from sled: {0}")]
     UpgradeSled(#[from] crate::sled_export::SledMigrationError),
@@ -689,6 +1034,10 @@ pub enum IambError {
     #[error("Matrix client storage error: {0}")]
     Store(#[from] matrix_sdk::StoreError),
 
+    /// A failure in the local message search index.
+    #[error("Message search index error: {0}")]
+    SearchIndex(#[from] rusqlite::Error),
+
     /// A failure during serialization or deserialization.
     #[error("Serialization/deserialization error: {0}")]
     Serde(#[from] serde_json::Error),
@@ -713,6 +1062,10 @@ pub enum IambError {
     #[error("Current window is not a room")]
     NoSelectedRoom,
 
+    /// A failure due to being unable to find a message.
+    #[error("Could not find message {0}")]
+    UnknownEvent(OwnedEventId),
+
     /// A failure due to not having an outstanding room invitation.
     #[error("You do not have a current invitation to this room")]
     NotInvited,
@@ -721,6 +1074,10 @@ pub enum IambError {
     #[error("You need to join the room before you can do that")]
     NotJoined,
 
+    /// A failure due to the current room not being a space.
+    #[error("Current room is not a space")]
+    NotSpace,
+
     /// An unknown room was specified.
     #[error("Unknown room identifier: {0}")]
     UnknownRoom(OwnedRoomId),
@@ -813,6 +1170,262 @@ impl UnreadInfo {
     }
 }
 
+/// Progress of redacting a user's messages in the background.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PurgeProgress {
+    /// Whether we're still looking through the room history for messages to redact.
+    pub scanning: bool,
+
+    /// How many messages have been found to redact so far.
+    pub found: usize,
+
+    /// How many messages have been successfully redacted.
+    pub redacted: usize,
+
+    /// How many messages we failed to redact.
+    pub failed: usize,
+}
+
+/// The question and possible answers of a poll.
+#[derive(Clone, Debug)]
+pub struct PollStart {
+    /// The user who started the poll.
+    pub sender: OwnedUserId,
+
+    /// The question being asked.
+    pub question: String,
+
+    /// The identifier and text of each possible answer.
+    pub answers: Vec<(String, String)>,
+
+    /// How many answers each user may select.
+    pub max_selections: usize,
+
+    /// Whether the results are hidden until the poll has ended.
+    pub undisclosed: bool,
+
+    /// Whether the poll was started with the stable `m.poll.start` event type, instead of the
+    /// unstable MSC3381 one.
+    pub stable: bool,
+}
+
+impl PollStart {
+    fn from_stable(sender: OwnedUserId, poll: &PollContentBlock) -> Self {
+        let question = poll.question.text.find_plain().unwrap_or_default().to_string();
+        let answers = poll
+            .answers
+            .iter()
+            .map(|a| (a.id.clone(), a.text.find_plain().unwrap_or(&a.id).to_string()))
+            .collect();
+
+        PollStart {
+            sender,
+            question,
+            answers,
+            max_selections: usize::try_from(u64::from(poll.max_selections)).unwrap_or(1),
+            undisclosed: matches!(poll.kind, PollKind::Undisclosed),
+            stable: true,
+        }
+    }
+
+    fn from_unstable(sender: OwnedUserId, poll: &UnstablePollStartContentBlock) -> Self {
+        let answers = poll.answers.iter().map(|a| (a.id.clone(), a.text.clone())).collect();
+
+        PollStart {
+            sender,
+            question: poll.question.text.clone(),
+            answers,
+            max_selections: usize::try_from(u64::from(poll.max_selections)).unwrap_or(1),
+            undisclosed: matches!(poll.kind, PollKind::Undisclosed),
+            stable: false,
+        }
+    }
+
+    /// Map a vote's selections to the indices of the answers they refer to.
+    ///
+    /// Unknown answers are dropped, and only the first [PollStart::max_selections] are counted.
+    fn selected(&self, selections: &[String]) -> Vec<usize> {
+        let mut selected = vec![];
+
+        for selection in selections {
+            let Some(idx) = self.answers.iter().position(|(id, _)| id == selection) else {
+                continue;
+            };
+
+            if !selected.contains(&idx) {
+                selected.push(idx);
+            }
+        }
+
+        selected.truncate(self.max_selections.max(1));
+
+        return selected;
+    }
+}
+
+/// A poll started in a room, and the votes that have been cast in it.
+///
+/// Votes, edits and the end of a poll can be loaded before the event that started it when
+/// fetching older messages, so they're kept until the start is known and they can be checked
+/// against who created the poll.
+#[derive(Clone, Debug, Default)]
+pub struct Poll {
+    /// The question and possible answers, once the event starting the poll has been loaded.
+    ///
+    /// This reflects the most recent edit made by the poll's creator.
+    pub start: Option<PollStart>,
+
+    /// When the poll was ended by its creator, if it has been.
+    pub ended: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// The poll as it was originally started.
+    original: Option<PollStart>,
+
+    /// Edits to the poll, and when they were made.
+    edits: Vec<(MilliSecondsSinceUnixEpoch, PollStart)>,
+
+    /// Who has tried to end the poll, and when.
+    ends: Vec<(OwnedUserId, MilliSecondsSinceUnixEpoch)>,
+
+    /// Every vote that each user has cast, ordered by when they cast them.
+    votes: HashMap<OwnedUserId, BTreeMap<MilliSecondsSinceUnixEpoch, Vec<String>>>,
+}
+
+impl Poll {
+    /// Record the event that started the poll.
+    pub fn set_start(&mut self, start: PollStart) {
+        self.original = Some(start);
+        self.update();
+    }
+
+    /// Record an edit to the poll, which is only used if it was made by the poll's creator.
+    pub fn edit(&mut self, ts: MilliSecondsSinceUnixEpoch, start: PollStart) {
+        self.edits.push((ts, start));
+        self.update();
+    }
+
+    /// Record a user's vote.
+    ///
+    /// Only each user's most recent vote from before the poll ended is counted.
+    pub fn vote(
+        &mut self,
+        user_id: OwnedUserId,
+        ts: MilliSecondsSinceUnixEpoch,
+        selections: Vec<String>,
+    ) {
+        self.votes.entry(user_id).or_default().insert(ts, selections);
+    }
+
+    /// Record a request to end the poll, which is only honoured if it came from the poll's
+    /// creator.
+    pub fn end(&mut self, user_id: OwnedUserId, ts: MilliSecondsSinceUnixEpoch) {
+        self.ends.push((user_id, ts));
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let Some(original) = &self.original else {
+            return;
+        };
+
+        let edit = self
+            .edits
+            .iter()
+            .filter(|(_, edit)| edit.sender == original.sender)
+            .max_by_key(|(ts, _)| *ts)
+            .map(|(_, edit)| edit);
+
+        self.start = Some(edit.unwrap_or(original).clone());
+        self.ended = self
+            .ends
+            .iter()
+            .filter(|(sender, _)| *sender == original.sender)
+            .map(|(_, ts)| *ts)
+            .min();
+    }
+
+    /// Get the selections of a user's most recent vote from before the poll ended.
+    fn selections<'a>(
+        &self,
+        votes: &'a BTreeMap<MilliSecondsSinceUnixEpoch, Vec<String>>,
+    ) -> Option<&'a Vec<String>> {
+        let vote = match self.ended {
+            Some(ended) => votes.range(..=ended).next_back(),
+            None => votes.last_key_value(),
+        };
+
+        vote.map(|(_, selections)| selections)
+    }
+
+    /// Count the votes for each answer, in the order that the answers are listed.
+    pub fn tally(&self) -> Vec<usize> {
+        let Some(start) = &self.start else {
+            return vec![];
+        };
+
+        let mut counts = vec![0; start.answers.len()];
+
+        for selections in self.votes.values().filter_map(|votes| self.selections(votes)) {
+            for idx in start.selected(selections) {
+                counts[idx] += 1;
+            }
+        }
+
+        return counts;
+    }
+
+    /// How many users have cast a valid vote.
+    pub fn voters(&self) -> usize {
+        let Some(start) = &self.start else {
+            return 0;
+        };
+
+        self.votes
+            .values()
+            .filter_map(|votes| self.selections(votes))
+            .filter(|selections| !start.selected(selections).is_empty())
+            .count()
+    }
+
+    /// Describe the poll's results as text, for clients that can't display polls.
+    pub fn results_text(&self) -> String {
+        let Some(start) = &self.start else {
+            return "The poll has ended".into();
+        };
+
+        let tally = self.tally();
+        let top = tally.iter().copied().max().unwrap_or_default();
+
+        if top == 0 {
+            return "The poll has ended with no votes".into();
+        }
+
+        let answers = start
+            .answers
+            .iter()
+            .zip(tally)
+            .filter(|(_, count)| *count == top)
+            .map(|((_, text), _)| text.as_str())
+            .collect::<Vec<_>>();
+
+        if let [answer] = answers.as_slice() {
+            format!("The poll has ended. Top answer: {answer}")
+        } else {
+            format!("The poll has ended. Top answers: {}", answers.join(", "))
+        }
+    }
+
+    /// Get the indices of the answers that a user has voted for.
+    pub fn user_selections(&self, user_id: &UserId) -> Vec<usize> {
+        let selections = self.votes.get(user_id).and_then(|votes| self.selections(votes));
+
+        match (&self.start, selections) {
+            (Some(start), Some(selections)) => start.selected(selections),
+            _ => vec![],
+        }
+    }
+}
+
 /// Information about room's the user's joined.
 #[derive(Default)]
 pub struct RoomInfo {
@@ -844,6 +1457,12 @@ pub struct RoomInfo {
     /// A map of message identifiers to thread replies.
     threads: HashMap<OwnedEventId, Messages>,
 
+    /// A map of edit identifiers to the identifiers of the messages they replaced.
+    replacements: HashMap<OwnedEventId, OwnedEventId>,
+
+    /// A map of poll identifiers to their answers and votes.
+    pub polls: HashMap<OwnedEventId, Poll>,
+
     /// Whether the scrollback for this room is currently being fetched.
     pub fetching: bool,
 
@@ -853,6 +1472,15 @@ pub struct RoomInfo {
     /// The time that we last fetched scrollback for this room.
     pub fetch_last: Option<Instant>,
 
+    /// The rooms inside of this space, or why they couldn't be fetched.
+    pub hierarchy: Option<Result<Vec<SpaceHierarchyRoomsChunk>, String>>,
+
+    /// Whether the hierarchy of this space is currently being fetched.
+    pub fetching_hierarchy: bool,
+
+    /// The time that we last finished fetching the hierarchy of this space.
+    pub hierarchy_last: Option<Instant>,
+
     /// Users currently typing in this room, and when we received notification of them doing so.
     pub users_typing: Option<(Instant, Vec<OwnedUserId>)>,
 
@@ -861,9 +1489,25 @@ pub struct RoomInfo {
 
     /// The last time the room was rendered, used to detect if it is currently open.
     pub draw_last: Option<Instant>,
+
+    /// Information about the room that replaced this one, if it has been upgraded.
+    pub tombstone: Option<RoomTombstoneEventContent>,
+
+    /// Progress of the `:room purge` currently running in this room, if any.
+    pub purge: Option<PurgeProgress>,
+
+    /// Members whose identity has changed since they were verified.
+    pub identity_violations: HashSet<OwnedUserId>,
 }
 
 impl RoomInfo {
+    /// Iterate over every loaded message, including thread replies.
+    pub fn all_messages(&self) -> impl Iterator<Item = (&MessageKey, &Message)> {
+        self.messages
+            .iter()
+            .chain(self.threads.values().flat_map(|thread| thread.iter()))
+    }
+
     pub fn get_thread(&self, root: Option<&EventId>) -> Option<&Messages> {
         if let Some(thread_root) = root {
             self.threads.get(thread_root)
@@ -953,6 +1597,8 @@ impl RoomInfo {
         match self.keys.get(redacts) {
             None => return,
             Some(EventLocation::Message(None, key)) => {
+                self.polls.remove(redacts);
+
                 if let Some(msg) = self.messages.get_mut(key) {
                     let ev = SyncRoomRedactionEvent::Original(ev);
                     msg.redact(ev, room_version);
@@ -998,6 +1644,80 @@ impl RoomInfo {
         }
     }
 
+    /// Insert an event that starts, votes in, or ends a poll.
+    pub fn insert_poll(&mut self, ev: AnyMessageLikeEvent) {
+        match ev {
+            AnyMessageLikeEvent::PollStart(MessageLikeEvent::Original(ev)) => {
+                let start = PollStart::from_stable(ev.sender.clone(), &ev.content.poll);
+
+                if let Some(Relation::Replacement(repl)) = &ev.content.relates_to {
+                    let poll = self.polls.entry(repl.event_id.clone()).or_default();
+                    poll.edit(ev.origin_server_ts, start);
+                    return;
+                }
+
+                let ts = ev.origin_server_ts;
+                self.insert_poll_start(ev.event_id, ev.sender, ts, ev.room_id, start);
+            },
+            AnyMessageLikeEvent::UnstablePollStart(MessageLikeEvent::Original(ev)) => {
+                let poll = ev.content.poll_start();
+                let start = PollStart::from_unstable(ev.sender.clone(), poll);
+
+                if let UnstablePollStartEventContent::Replacement(repl) = &ev.content {
+                    let poll = self.polls.entry(repl.relates_to.event_id.clone()).or_default();
+                    poll.edit(ev.origin_server_ts, start);
+                    return;
+                }
+
+                let ts = ev.origin_server_ts;
+                self.insert_poll_start(ev.event_id, ev.sender, ts, ev.room_id, start);
+            },
+            AnyMessageLikeEvent::PollResponse(MessageLikeEvent::Original(ev)) => {
+                let poll = self.polls.entry(ev.content.relates_to.event_id).or_default();
+                let selections = ev.content.selections.to_vec();
+                poll.vote(ev.sender, ev.origin_server_ts, selections);
+            },
+            AnyMessageLikeEvent::UnstablePollResponse(MessageLikeEvent::Original(ev)) => {
+                let poll = self.polls.entry(ev.content.relates_to.event_id).or_default();
+                let selections = ev.content.poll_response.answers;
+                poll.vote(ev.sender, ev.origin_server_ts, selections);
+            },
+            AnyMessageLikeEvent::PollEnd(MessageLikeEvent::Original(ev)) => {
+                let poll = self.polls.entry(ev.content.relates_to.event_id).or_default();
+                poll.end(ev.sender, ev.origin_server_ts);
+            },
+            AnyMessageLikeEvent::UnstablePollEnd(MessageLikeEvent::Original(ev)) => {
+                let poll = self.polls.entry(ev.content.relates_to.event_id).or_default();
+                poll.end(ev.sender, ev.origin_server_ts);
+            },
+            _ => return,
+        }
+    }
+
+    /// Insert the start of a poll into the scrollback as a message with its question as the
+    /// body, so that it can be selected, replied to and redacted like any other message.
+    fn insert_poll_start(
+        &mut self,
+        event_id: OwnedEventId,
+        sender: OwnedUserId,
+        origin_server_ts: MilliSecondsSinceUnixEpoch,
+        room_id: OwnedRoomId,
+        start: PollStart,
+    ) {
+        let content = RoomMessageEventContent::text_plain(format!("Poll: {}", start.question));
+        let msg = OriginalRoomMessageEvent {
+            content,
+            event_id: event_id.clone(),
+            sender,
+            origin_server_ts,
+            room_id,
+            unsigned: MessageLikeUnsigned::new(),
+        };
+
+        self.polls.entry(event_id).or_default().set_start(start);
+        self.insert_message(RoomMessageEvent::Original(msg));
+    }
+
     /// Insert an edit.
     pub fn insert_edit(&mut self, msg: Replacement<RoomMessageEventContentWithoutRelation>) {
         let event_id = msg.event_id;
@@ -1048,6 +1768,61 @@ impl RoomInfo {
         }
     }
 
+    /// Record how a message was encrypted, so that its trustworthiness can be shown.
+    pub fn set_encryption(&mut self, event_id: &EventId, encryption: Option<MessageEncryption>) {
+        if let Some(original) = self.replacements.get(event_id).cloned() {
+            // An edit can't make a message look more trustworthy than it already was.
+            if let Some(msg) = self.get_message_mut(&original) {
+                msg.encryption = MessageEncryption::weaker(msg.encryption.take(), encryption);
+            }
+
+            return;
+        }
+
+        if let Some(msg) = self.get_message_mut(event_id) {
+            msg.encryption = encryption;
+        }
+    }
+
+    /// Get a message as mutable, whether it's in the main timeline or a thread.
+    fn get_message_mut(&mut self, event_id: &EventId) -> Option<&mut Message> {
+        match self.keys.get(event_id)? {
+            EventLocation::Message(None, key) => self.messages.get_mut(key),
+            EventLocation::Message(Some(root), key) => {
+                self.threads.get_mut(root).and_then(|thread| thread.get_mut(key))
+            },
+            _ => None,
+        }
+    }
+
+    /// Get the loaded messages that couldn't be decrypted, optionally limited to those that
+    /// were encrypted with a specific Megolm session.
+    pub fn get_undecrypted(&self, session_id: Option<&str>) -> Vec<OriginalRoomEncryptedEvent> {
+        self.messages
+            .values()
+            .filter(|msg| session_id.is_none() || msg.event.megolm_session_id() == session_id)
+            .filter_map(|msg| {
+                match &msg.event {
+                    MessageEvent::EncryptedOriginal(ev) => Some(ev.as_ref().clone()),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Remove a message that couldn't be decrypted, so that it can be replaced with the
+    /// decrypted event.
+    pub fn remove_undecrypted(&mut self, event_id: &EventId) {
+        if let Some(EventLocation::Message(None, key)) = self.keys.get(event_id) {
+            if let Some(msg) = self.messages.get(key) {
+                if matches!(msg.event, MessageEvent::EncryptedOriginal(_)) {
+                    self.messages.remove(key);
+                    self.keys.remove(event_id);
+                }
+            }
+        }
+    }
+
     /// Inserts events that couldn't be decrypted into the scrollback.
     pub fn insert_encrypted(&mut self, msg: RoomEncryptedEvent) {
         let event_id = msg.event_id().to_owned();
@@ -1085,7 +1860,11 @@ impl RoomInfo {
                 ..
             }) => {
                 match relates_to {
-                    Relation::Replacement(repl) => self.insert_edit(repl.clone()),
+                    Relation::Replacement(repl) => {
+                        let edit_id = msg.event_id().to_owned();
+                        self.replacements.insert(edit_id, repl.event_id.clone());
+                        self.insert_edit(repl.clone());
+                    },
                     Relation::Thread(Thread { event_id, .. }) => {
                         let event_id = event_id.clone();
                         self.insert_thread(msg, event_id);
@@ -1098,18 +1877,20 @@ impl RoomInfo {
         }
     }
 
-    /// Insert a new message event, and spawn a task for image-preview if it has an image
-    /// attachment.
+    /// Insert a new message event, add it to the search index, and spawn a task for
+    /// image-preview if it has an image attachment.
     pub fn insert_with_preview(
         &mut self,
-        room_id: OwnedRoomId,
+        index: &MessageIndex,
         store: AsyncProgramStore,
         picker: Option<Picker>,
         ev: RoomMessageEvent,
         settings: &mut ApplicationSettings,
         media: matrix_sdk::Media,
     ) {
+        let room_id = ev.room_id().to_owned();
         let source = picker.and_then(|_| source_from_event(&ev));
+        index.insert(&ev);
         self.insert(ev);
 
         if let Some((event_id, source)) = source {
@@ -1236,6 +2017,39 @@ impl RoomInfo {
         return top;
     }
 
+    /// Create a [Rect] that shows a banner pointing to this room's successor, if it has one.
+    pub fn render_tombstone(&self, area: Rect, buf: &mut Buffer) -> Rect {
+        if area.height <= 2 || area.width <= 20 {
+            return area;
+        }
+
+        let Some(tombstone) = &self.tombstone else {
+            return area;
+        };
+
+        let top = Rect::new(area.x, area.y + 1, area.width, area.height - 1);
+        let bar = Rect::new(area.x, area.y, area.width, 1);
+        let bold = Style::default().add_modifier(StyleModifier::BOLD);
+
+        let body = if tombstone.body.is_empty() {
+            "This room has been replaced"
+        } else {
+            tombstone.body.as_str()
+        };
+
+        let line = Line::from(vec![
+            Span::styled(body.trim_end_matches('.'), bold),
+            Span::raw(": use "),
+            Span::styled(":successor", bold),
+            Span::raw(" to join "),
+            Span::raw(tombstone.replacement_room.as_str()),
+        ]);
+
+        Paragraph::new(line).alignment(Alignment::Center).render(bar, buf);
+
+        return top;
+    }
+
     /// Checks if a given user has reacted with the given emoji on the given event
     pub fn user_reactions_contains(
         &mut self,
@@ -1347,6 +2161,7 @@ bitflags::bitflags! {
         const EMPTY = 0b00000000;
         const MESSAGES = 0b00000001;
         const MEMBERS =  0b00000010;
+        const HIERARCHY = 0b00000100;
     }
 }
 
@@ -1376,6 +2191,64 @@ impl IntoIterator for RoomNeeds {
     }
 }
 
+/// A message found while searching.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    /// The room that the message was sent in.
+    pub room_id: OwnedRoomId,
+
+    /// The identifier for the message.
+    pub event_id: OwnedEventId,
+
+    /// The user who sent the message.
+    pub sender: OwnedUserId,
+
+    /// When the message was sent.
+    pub timestamp: MessageTimeStamp,
+
+    /// The text of the message.
+    pub body: String,
+}
+
+/// The results of the most recent search.
+#[derive(Default)]
+pub struct SearchResults {
+    /// The terms that were searched for.
+    pub term: String,
+
+    /// The messages that matched.
+    pub results: Vec<SearchResult>,
+}
+
+/// The rooms listed by the most recent public room directory search.
+#[derive(Default)]
+pub struct DirectoryResults {
+    /// The server whose directory is being listed, or [None] for our homeserver.
+    pub server: Option<OwnedServerName>,
+
+    /// The filter applied to the room list.
+    pub filter: Option<String>,
+
+    /// The rooms fetched so far.
+    pub rooms: Vec<PublicRoomsChunk>,
+
+    /// The token for fetching the next page of rooms, if there is one.
+    pub next_batch: Option<String>,
+}
+
+/// One of the user's devices, as listed in the `:devices` window.
+#[derive(Clone)]
+pub struct DeviceEntry {
+    /// The device's details from the homeserver.
+    pub device: MatrixDevice,
+
+    /// Whether the device has been verified.
+    pub verified: bool,
+
+    /// Whether this is the device that iamb is running as.
+    pub current: bool,
+}
+
 /// The main application state.
 pub struct ChatStore {
     /// `:`-commands
@@ -1390,12 +2263,33 @@ pub struct ChatStore {
     /// Map of room names.
     pub names: CompletionMap<String, OwnedRoomId>,
 
+    /// Users whose messages, reactions and typing notices are hidden.
+    pub ignored: HashSet<OwnedUserId>,
+
     /// Presence information for other users.
     pub presences: CompletionMap<OwnedUserId, PresenceState>,
 
+    /// The presence we're showing to other users, which gets sent with each sync.
+    pub presence: PresenceState,
+
+    /// The status message we're showing alongside our presence.
+    pub status_msg: Option<String>,
+
     /// In-progress and completed verifications.
     pub verifications: HashMap<String, SasVerification>,
 
+    /// Results from the last `:search`.
+    pub search: SearchResults,
+
+    /// Rooms from the last `:directory` search.
+    pub directory: DirectoryResults,
+
+    /// Devices fetched for the `:devices` window.
+    pub devices: Vec<DeviceEntry>,
+
+    /// Full-text index of the messages we've seen, for searching offline.
+    pub index: MessageIndex,
+
     /// Settings for the current profile loaded from config file.
     pub settings: ApplicationSettings,
 
@@ -1419,12 +2313,20 @@ pub struct ChatStore {
 
     /// Whether the application is currently focused
     pub focused: bool,
+
+    /// Messages from background tasks to show the next time we redraw.
+    pub notices: Vec<InfoMessage>,
 }
 
 impl ChatStore {
     /// Create a new [ChatStore].
     pub fn new(worker: Requester, settings: ApplicationSettings) -> Self {
         let picker = picker_from_settings(&settings);
+        let index = if settings.tunables.search_index {
+            MessageIndex::open(settings.search_db.as_path())
+        } else {
+            MessageIndex::in_memory()
+        };
 
         ChatStore {
             worker,
@@ -1434,17 +2336,30 @@ impl ChatStore {
             emojis: emoji_map(),
 
             names: Default::default(),
+            ignored: Default::default(),
             rooms: Default::default(),
             presences: Default::default(),
+            presence: PresenceState::Online,
+            status_msg: None,
             verifications: Default::default(),
+            search: Default::default(),
+            directory: Default::default(),
+            devices: Default::default(),
+            index,
             need_load: Default::default(),
             sync_info: Default::default(),
             draw_curr: None,
             ring_bell: false,
             focused: true,
+            notices: vec![],
         }
     }
 
+    /// Whether the user has chosen to ignore everything sent by the given user.
+    pub fn is_ignored(&self, user_id: &UserId) -> bool {
+        self.ignored.contains(user_id)
+    }
+
     /// Get a joined room.
     pub fn get_joined_room(&self, room_id: &RoomId) -> Option<MatrixRoom> {
         let room = self.worker.client.get_room(room_id)?;
@@ -1497,6 +2412,9 @@ pub enum IambId {
     /// The `:members` window for a given Matrix room.
     MemberList(OwnedRoomId),
 
+    /// The `:pins` window for a given Matrix room.
+    PinnedList(OwnedRoomId),
+
     /// The `:rooms` window.
     RoomList,
 
@@ -1514,6 +2432,18 @@ pub enum IambId {
 
     /// The `:unreads` window.
     UnreadList,
+
+    /// The `:search` results window.
+    SearchResults,
+
+    /// The `:directory` window.
+    Directory,
+
+    /// The `:devices` window.
+    DeviceList,
+
+    /// The `:source` window for a message in a Matrix room.
+    Source(OwnedRoomId, OwnedEventId),
 }
 
 impl Display for IambId {
@@ -1528,6 +2458,9 @@ impl Display for IambId {
             IambId::MemberList(room_id) => {
                 write!(f, "iamb://members/{room_id}")
             },
+            IambId::PinnedList(room_id) => {
+                write!(f, "iamb://pins/{room_id}")
+            },
             IambId::DirectList => f.write_str("iamb://dms"),
             IambId::RoomList => f.write_str("iamb://rooms"),
             IambId::SpaceList => f.write_str("iamb://spaces"),
@@ -1535,6 +2468,12 @@ impl Display for IambId {
             IambId::Welcome => f.write_str("iamb://welcome"),
             IambId::ChatList => f.write_str("iamb://chats"),
             IambId::UnreadList => f.write_str("iamb://unreads"),
+            IambId::SearchResults => f.write_str("iamb://search"),
+            IambId::Directory => f.write_str("iamb://directory"),
+            IambId::DeviceList => f.write_str("iamb://devices"),
+            IambId::Source(room_id, event_id) => {
+                write!(f, "iamb://source/{room_id}/{event_id}")
+            },
         }
     }
 }
@@ -1624,6 +2563,21 @@ impl<'de> Visitor<'de> for IambIdVisitor {
 
                 Ok(IambId::MemberList(room_id))
             },
+            Some("pins") => {
+                let Some(path) = url.path_segments() else {
+                    return Err(E::custom("Invalid pins window URL"));
+                };
+
+                let &[room_id] = path.collect::<Vec<_>>().as_slice() else {
+                    return Err(E::custom("Invalid pins window URL"));
+                };
+
+                let Ok(room_id) = OwnedRoomId::try_from(room_id) else {
+                    return Err(E::custom("Invalid room identifier"));
+                };
+
+                Ok(IambId::PinnedList(room_id))
+            },
             Some("dms") => {
                 if url.path() != "" {
                     return Err(E::custom("iamb://dms takes no path"));
@@ -1673,6 +2627,46 @@ impl<'de> Visitor<'de> for IambIdVisitor {
 
                 Ok(IambId::UnreadList)
             },
+            Some("search") => {
+                if url.path() != "" {
+                    return Err(E::custom("iamb://search takes no path"));
+                }
+
+                Ok(IambId::SearchResults)
+            },
+            Some("directory") => {
+                if url.path() != "" {
+                    return Err(E::custom("iamb://directory takes no path"));
+                }
+
+                Ok(IambId::Directory)
+            },
+            Some("devices") => {
+                if url.path() != "" {
+                    return Err(E::custom("iamb://devices takes no path"));
+                }
+
+                Ok(IambId::DeviceList)
+            },
+            Some("source") => {
+                let Some(path) = url.path_segments() else {
+                    return Err(E::custom("Invalid source window URL"));
+                };
+
+                let &[room_id, event_id] = path.collect::<Vec<_>>().as_slice() else {
+                    return Err(E::custom("Invalid source window URL"));
+                };
+
+                let Ok(room_id) = OwnedRoomId::try_from(room_id) else {
+                    return Err(E::custom("Invalid room identifier"));
+                };
+
+                let Ok(event_id) = OwnedEventId::try_from(event_id) else {
+                    return Err(E::custom("Invalid event identifier"));
+                };
+
+                Ok(IambId::Source(room_id, event_id))
+            },
             Some(s) => Err(E::custom(format!("{s:?} is not a valid window"))),
             None => Err(E::custom("Invalid iamb window URL")),
         }
@@ -1719,6 +2713,9 @@ pub enum IambBufferId {
     /// The `:members` window for a room.
     MemberList(OwnedRoomId),
 
+    /// The `:pins` window for a room.
+    PinnedList(OwnedRoomId),
+
     /// The `:rooms` window.
     RoomList,
 
@@ -1736,6 +2733,18 @@ pub enum IambBufferId {
 
     /// The `:unreads` window.
     UnreadList,
+
+    /// The `:search` results window.
+    SearchResults,
+
+    /// The `:directory` window.
+    Directory,
+
+    /// The `:devices` window.
+    DeviceList,
+
+    /// The buffer for the `:source` window.
+    Source(OwnedRoomId, OwnedEventId),
 }
 
 impl IambBufferId {
@@ -1746,12 +2755,17 @@ impl IambBufferId {
             IambBufferId::Room(room, thread, _) => IambId::Room(room.clone(), thread.clone()),
             IambBufferId::DirectList => IambId::DirectList,
             IambBufferId::MemberList(room) => IambId::MemberList(room.clone()),
+            IambBufferId::PinnedList(room) => IambId::PinnedList(room.clone()),
             IambBufferId::RoomList => IambId::RoomList,
             IambBufferId::SpaceList => IambId::SpaceList,
             IambBufferId::VerifyList => IambId::VerifyList,
             IambBufferId::Welcome => IambId::Welcome,
             IambBufferId::ChatList => IambId::ChatList,
             IambBufferId::UnreadList => IambId::UnreadList,
+            IambBufferId::SearchResults => IambId::SearchResults,
+            IambBufferId::Directory => IambId::Directory,
+            IambBufferId::DeviceList => IambId::DeviceList,
+            IambBufferId::Source(room, event) => IambId::Source(room.clone(), event.clone()),
         };
 
         Some(id)
@@ -1781,12 +2795,17 @@ impl ApplicationInfo for IambInfo {
 
             IambBufferId::DirectList => vec![],
             IambBufferId::MemberList(_) => vec![],
+            IambBufferId::PinnedList(_) => vec![],
             IambBufferId::RoomList => vec![],
             IambBufferId::SpaceList => vec![],
             IambBufferId::VerifyList => vec![],
             IambBufferId::Welcome => vec![],
             IambBufferId::ChatList => vec![],
             IambBufferId::UnreadList => vec![],
+            IambBufferId::SearchResults => vec![],
+            IambBufferId::Directory => vec![],
+            IambBufferId::DeviceList => vec![],
+            IambBufferId::Source(_, _) => vec![],
         }
     }
 
@@ -1923,14 +2942,18 @@ fn complete_cmdarg(
     };
 
     match cmd.name.as_str() {
-        "cancel" | "dms" | "edit" | "redact" | "reply" => vec![],
-        "members" | "rooms" | "spaces" | "welcome" => vec![],
+        "cancel" | "dms" | "edit" | "pin" | "redact" | "reply" | "unpin" => vec![],
+        "successor" => vec![],
+        "members" | "pins" | "rooms" | "spaces" | "welcome" => vec![],
         "download" | "keys" | "open" | "upload" => complete_path(text, cursor),
         "react" | "unreact" => complete_emoji(text, cursor, store),
 
-        "invite" => complete_users(text, cursor, store),
-        "join" | "split" | "vsplit" | "tabedit" => complete_matrix_names(text, cursor, store),
+        "invite" | "ignore" | "unignore" => complete_users(text, cursor, store),
+        "join" | "knock" | "split" | "vsplit" | "tabedit" => {
+            complete_matrix_names(text, cursor, store)
+        },
         "room" => vec![],
+        "space" => complete_matrix_names(text, cursor, store),
         "verify" => vec![],
         "vertical" | "horizontal" | "aboveleft" | "belowright" | "tab" => {
             complete_cmd(desc.arg.text.as_str(), text, cursor, store)
@@ -2047,6 +3070,171 @@ pub mod tests {
         ]);
     }
 
+    #[test]
+    fn test_edit_encryption() {
+        use matrix_sdk::deserialized_responses::{
+            AlgorithmInfo,
+            EncryptionInfo,
+            VerificationState,
+        };
+        use matrix_sdk::ruma::events::room::message::MessageType;
+
+        let mut info = RoomInfo::default();
+        let original_id = owned_event_id!("$original");
+        let message = |content, event_id: &str| {
+            RoomMessageEvent::Original(OriginalRoomMessageEvent {
+                content,
+                event_id: OwnedEventId::from_str(event_id).unwrap(),
+                sender: owned_user_id!("@foo:example.org"),
+                origin_server_ts: MilliSecondsSinceUnixEpoch::now(),
+                room_id: owned_room_id!("!foo:example.org"),
+                unsigned: MessageLikeUnsigned::new(),
+            })
+        };
+
+        let verified = EncryptionInfo {
+            sender: owned_user_id!("@foo:example.org"),
+            sender_device: None,
+            algorithm_info: AlgorithmInfo::MegolmV1AesSha2 {
+                curve25519_key: "key".into(),
+                sender_claimed_keys: Default::default(),
+            },
+            verification_state: VerificationState::Verified,
+        };
+
+        let content = RoomMessageEventContent::text_plain("hello");
+        info.insert(message(content, "$original"));
+        info.set_encryption(&original_id, MessageEncryption::new(true, Some(&verified)));
+
+        let msg = info.get_event(&original_id).unwrap();
+        assert!(matches!(msg.encryption, Some(MessageEncryption::Decrypted(_))));
+
+        // An unencrypted edit downgrades the verified message.
+        let mut content = RoomMessageEventContent::text_plain("* goodbye");
+        let new_content = MessageType::text_plain("goodbye").into();
+        content.relates_to =
+            Some(Relation::Replacement(Replacement::new(original_id.clone(), new_content)));
+        info.insert(message(content, "$edit"));
+        info.set_encryption(&owned_event_id!("$edit"), MessageEncryption::new(true, None));
+
+        let msg = info.get_event(&original_id).unwrap();
+        assert_eq!(msg.event.body(), "goodbye");
+        assert!(matches!(msg.encryption, Some(MessageEncryption::Unencrypted)));
+
+        // A verified edit can't upgrade it again.
+        let mut content = RoomMessageEventContent::text_plain("* hello again");
+        let new_content = MessageType::text_plain("hello again").into();
+        content.relates_to =
+            Some(Relation::Replacement(Replacement::new(original_id.clone(), new_content)));
+        info.insert(message(content, "$edit2"));
+        info.set_encryption(
+            &owned_event_id!("$edit2"),
+            MessageEncryption::new(true, Some(&verified)),
+        );
+
+        let msg = info.get_event(&original_id).unwrap();
+        assert!(matches!(msg.encryption, Some(MessageEncryption::Unencrypted)));
+    }
+
+    #[test]
+    fn test_poll_tally() {
+        use matrix_sdk::ruma::{
+            events::poll::{
+                unstable_end::UnstablePollEndEventContent,
+                unstable_response::UnstablePollResponseEventContent,
+                unstable_start::{
+                    NewUnstablePollStartEventContent,
+                    ReplacementUnstablePollStartEventContent,
+                    UnstablePollAnswer,
+                    UnstablePollAnswers,
+                },
+            },
+            events::{MessageLikeEventContent, RedactContent, RedactedMessageLikeEventContent},
+            user_id,
+            UInt,
+        };
+
+        fn original<C>(content: C, event_id: &str, sender: &str, ts: u32) -> MessageLikeEvent<C>
+        where
+            C: MessageLikeEventContent + RedactContent,
+            C::Redacted: RedactedMessageLikeEventContent,
+        {
+            MessageLikeEvent::Original(matrix_sdk::ruma::events::OriginalMessageLikeEvent {
+                content,
+                event_id: OwnedEventId::from_str(event_id).unwrap(),
+                sender: OwnedUserId::from_str(sender).unwrap(),
+                origin_server_ts: MilliSecondsSinceUnixEpoch(UInt::from(ts)),
+                room_id: owned_room_id!("!foo:example.org"),
+                unsigned: MessageLikeUnsigned::new(),
+            })
+        }
+
+        let mut info = RoomInfo::default();
+        let poll_id = owned_event_id!("$poll");
+        let vote = |answer: &str, event_id, sender, ts| {
+            let answers = vec![answer.to_string()];
+            let content = UnstablePollResponseEventContent::new(answers, poll_id.clone());
+            AnyMessageLikeEvent::UnstablePollResponse(original(content, event_id, sender, ts))
+        };
+
+        let start = |question: &str, event_id, sender, ts, replaces: Option<OwnedEventId>| {
+            let answers = vec![
+                UnstablePollAnswer::new("tacos", "Tacos"),
+                UnstablePollAnswer::new("ramen", "Ramen"),
+            ];
+            let answers = UnstablePollAnswers::try_from(answers).unwrap();
+            let start = UnstablePollStartContentBlock::new(question, answers);
+            let content = match replaces {
+                Some(replaces) => {
+                    ReplacementUnstablePollStartEventContent::new(start, replaces).into()
+                },
+                None => NewUnstablePollStartEventContent::new(start).into(),
+            };
+            let ev = original::<UnstablePollStartEventContent>(content, event_id, sender, ts);
+            AnyMessageLikeEvent::UnstablePollStart(ev)
+        };
+        let end = |event_id, sender, ts| {
+            let content = UnstablePollEndEventContent::new("The poll has ended", poll_id.clone());
+            AnyMessageLikeEvent::UnstablePollEnd(original(content, event_id, sender, ts))
+        };
+
+        // Votes, edits and ends can be loaded before the poll itself when fetching older
+        // messages, and only edits and ends from the poll's creator are used.
+        info.insert_poll(start("Dinner?", "$edit1", "@foo:example.org", 3, Some(poll_id.clone())));
+        info.insert_poll(start("Spam?", "$edit2", "@bar:example.org", 4, Some(poll_id.clone())));
+        info.insert_poll(end("$end1", "@bar:example.org", 3));
+        info.insert_poll(vote("tacos", "$vote1", "@foo:example.org", 2));
+        info.insert_poll(start("Lunch?", "$poll", "@foo:example.org", 1, None));
+
+        let poll = info.polls.get(&poll_id).unwrap();
+        assert_eq!(poll.start.as_ref().unwrap().question, "Dinner?");
+        assert_eq!(poll.ended, None);
+
+        info.insert_poll(vote("ramen", "$vote2", "@bar:example.org", 3));
+        info.insert_poll(vote("tacos", "$vote3", "@bar:example.org", 4));
+        info.insert_poll(vote("sushi", "$vote4", "@baz:example.org", 5));
+
+        let poll = info.polls.get(&poll_id).unwrap();
+        assert_eq!(poll.tally(), vec![2, 0]);
+        assert_eq!(poll.voters(), 2);
+        assert_eq!(poll.user_selections(user_id!("@bar:example.org")), vec![0]);
+        assert_eq!(poll.user_selections(user_id!("@baz:example.org")), Vec::<usize>::new());
+
+        info.insert_poll(end("$end2", "@foo:example.org", 6));
+
+        // Votes cast after the poll has ended don't count.
+        info.insert_poll(vote("ramen", "$vote5", "@qux:example.org", 7));
+        info.insert_poll(vote("ramen", "$vote6", "@bar:example.org", 8));
+
+        let poll = info.polls.get(&poll_id).unwrap();
+        assert_eq!(poll.ended, Some(MilliSecondsSinceUnixEpoch(UInt::from(6u32))));
+        assert_eq!(poll.tally(), vec![2, 0]);
+        assert_eq!(poll.results_text(), "The poll has ended. Top answer: Tacos");
+
+        let msg = info.get_event(&poll_id).unwrap();
+        assert_eq!(msg.event.body(), "Poll: Lunch?");
+    }
+
     #[test]
     fn test_typing_spans() {
         let mut info = RoomInfo::default();
@@ -3,8 +3,14 @@
 //! The command-bar commands are set up here, and iamb-specific commands are defined here. See
 //! [modalkit::env::vim::command] for additional Vim commands we pull in.
 use std::convert::TryFrom;
+use std::time::Duration;
 
-use matrix_sdk::ruma::{events::tag::TagName, OwnedUserId};
+use matrix_sdk::ruma::{
+    events::tag::TagName,
+    presence::PresenceState,
+    OwnedServerName,
+    OwnedUserId,
+};
 
 use modalkit::{
     commands::{CommandError, CommandResult, CommandStep},
@@ -15,24 +21,55 @@ use modalkit::{
 use crate::base::{
     CreateRoomFlags,
     CreateRoomType,
+    DevicesAction,
+    DirectoryAction,
     DownloadFlags,
     HomeserverAction,
     IambAction,
     IambId,
+    IdentityAction,
     KeysAction,
     MemberUpdateAction,
     MessageAction,
+    ProfileAction,
     ProgramCommand,
     ProgramCommands,
+    PurgeLimit,
+    RawJsonAction,
     RoomAction,
     RoomField,
+    SearchFlags,
     SendAction,
+    ServerAclAction,
     VerifyAction,
 };
 
 type ProgContext = CommandContext;
 type ProgResult = CommandResult<ProgramCommand>;
 
+/// How many messages `:room purge` redacts when no count or duration is given.
+const PURGE_DEFAULT_COUNT: usize = 50;
+
+/// Parse how far back `:room purge` should go, either as a number of messages, or as a duration
+/// like `30m`, `12h` or `7d`.
+fn purge_limit(arg: &str) -> Option<PurgeLimit> {
+    if let Ok(n) = arg.parse::<usize>() {
+        return Some(PurgeLimit::Count(n));
+    }
+
+    let unit = arg.chars().last()?;
+    let n = arg[..arg.len() - unit.len_utf8()].parse::<u64>().ok()?;
+    let secs = match unit {
+        's' => n,
+        'm' => n.checked_mul(60)?,
+        'h' => n.checked_mul(60 * 60)?,
+        'd' => n.checked_mul(24 * 60 * 60)?,
+        _ => return None,
+    };
+
+    Some(PurgeLimit::Since(Duration::from_secs(secs)))
+}
+
 /// Convert strings the user types into a tag name.
 fn tag_name(name: String) -> Result<TagName, CommandError> {
     let tag = match name.to_lowercase().as_str() {
@@ -57,6 +94,24 @@ fn tag_name(name: String) -> Result<TagName, CommandError> {
     Ok(tag)
 }
 
+/// Convert the target of a `:room power` command into the [RoomField] it refers to.
+///
+/// Matrix user identifiers refer to a user's power level, and anything else is treated as an
+/// event type.
+fn power_field(target: String) -> Result<RoomField, CommandError> {
+    if target.starts_with('@') {
+        let Ok(user_id) = OwnedUserId::try_from(target.as_str()) else {
+            let msg = format!("Invalid user identifier: {target}");
+
+            return Err(CommandError::Error(msg));
+        };
+
+        Ok(RoomField::PowerLevel(user_id))
+    } else {
+        Ok(RoomField::EventPowerLevel(target.into()))
+    }
+}
+
 fn iamb_invite(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     let args = desc.arg.strings()?;
 
@@ -107,6 +162,17 @@ fn iamb_invite(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
 fn iamb_keys(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     let mut args = desc.arg.strings()?;
 
+    match args.first().map(String::as_str) {
+        Some("backup") => return iamb_keys_backup(args.split_off(1), ctx),
+        Some("request") if args.len() == 1 => {
+            let ract = IambAction::from(MessageAction::DownloadBackupKey);
+            let step = CommandStep::Continue(ract.into(), ctx.context.clone());
+
+            return Ok(step);
+        },
+        _ => {},
+    }
+
     if args.len() != 3 {
         return Err(CommandError::InvalidArgument);
     }
@@ -127,6 +193,28 @@ fn iamb_keys(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     return Ok(step);
 }
 
+fn iamb_keys_backup(mut args: Vec<String>, ctx: &mut ProgContext) -> ProgResult {
+    if args.is_empty() {
+        return Err(CommandError::InvalidArgument);
+    }
+
+    let act = args.remove(0);
+
+    let act = match (act.as_str(), args.len()) {
+        ("enable", 0) => KeysAction::BackupEnable(None),
+        ("enable", 1) => KeysAction::BackupEnable(args.pop()),
+        ("status", 0) => KeysAction::BackupStatus,
+        // Recovery keys are written as space-separated groups of characters.
+        ("restore", 1..) => KeysAction::BackupRestore(args.join(" ")),
+        _ => return Err(CommandError::InvalidArgument),
+    };
+
+    let vact = IambAction::Keys(act);
+    let step = CommandStep::Continue(vact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
 fn iamb_verify(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     let mut args = desc.arg.strings()?;
 
@@ -152,6 +240,13 @@ fn iamb_verify(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
 
                     return Ok(step);
                 },
+                "withdraw" => {
+                    let user = identity_user(args.remove(1))?;
+                    let iact = IambAction::from(IdentityAction::Withdraw(user));
+                    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+                    return Ok(step);
+                },
                 _ => return Result::Err(CommandError::InvalidArgument),
             };
 
@@ -166,6 +261,67 @@ fn iamb_verify(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     }
 }
 
+/// Parse the user identifier given to `:whois` or `:verify withdraw`.
+fn identity_user(arg: String) -> Result<OwnedUserId, CommandError> {
+    let Ok(user_id) = OwnedUserId::try_from(arg.as_str()) else {
+        let msg = format!("Invalid user identifier: {arg}");
+
+        return Err(CommandError::Error(msg));
+    };
+
+    Ok(user_id)
+}
+
+fn iamb_whois(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.len() != 1 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let user = identity_user(args.remove(0))?;
+    let iact = IambAction::from(IdentityAction::Show(user));
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_devices(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.is_empty() {
+        let iact = IambAction::from(DevicesAction::List);
+        let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+        return Ok(step);
+    }
+
+    let action = args.remove(0);
+    let mut args = args.into_iter();
+
+    let dact = match (action.as_str(), args.next(), args.next(), args.next()) {
+        // :devices rename <device-id> <name>
+        ("rename", Some(device_id), Some(name), None) => {
+            DevicesAction::Rename(device_id.into(), name)
+        },
+
+        // :devices delete <device-id>
+        ("delete", Some(device_id), None, None) => {
+            DevicesAction::Delete(device_id.into(), desc.bang)
+        },
+
+        // :devices verify <device-id>
+        ("verify", Some(device_id), None, None) => DevicesAction::Verify(device_id.into()),
+
+        _ => return Result::Err(CommandError::InvalidArgument),
+    };
+
+    let iact = IambAction::from(dact);
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
 fn iamb_dms(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     if !desc.arg.text.is_empty() {
         return Result::Err(CommandError::InvalidArgument);
@@ -188,6 +344,17 @@ fn iamb_members(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     return Ok(step);
 }
 
+fn iamb_pins(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    if !desc.arg.text.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let open = IambAction::Room(RoomAction::Pins(ctx.clone().into()));
+    let step = CommandStep::Continue(open.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
 fn iamb_leave(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     if !desc.arg.text.is_empty() {
         return Result::Err(CommandError::InvalidArgument);
@@ -263,6 +430,39 @@ fn iamb_redact(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     return Ok(step);
 }
 
+fn iamb_poll(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let action = args.remove(0);
+
+    let iact = match (action.as_str(), args.len()) {
+        ("create", 2..) => {
+            let question = args.remove(0);
+
+            IambAction::from(SendAction::Poll(question, args))
+        },
+        ("end", 0) => IambAction::from(MessageAction::PollEnd),
+        ("vote", 1) => {
+            let Ok(answer) = args[0].parse::<usize>() else {
+                let msg = format!("Invalid answer number: {}", args[0]);
+
+                return Err(CommandError::Error(msg));
+            };
+
+            IambAction::from(MessageAction::PollVote(answer))
+        },
+        _ => return Result::Err(CommandError::InvalidArgument),
+    };
+
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
 fn iamb_reply(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     if !desc.arg.text.is_empty() {
         return Result::Err(CommandError::InvalidArgument);
@@ -274,6 +474,50 @@ fn iamb_reply(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     return Ok(step);
 }
 
+fn iamb_source(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    if !desc.arg.text.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let sact = IambAction::from(MessageAction::Source);
+    let step = CommandStep::Continue(sact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_encryption(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    if !desc.arg.text.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let eact = IambAction::from(MessageAction::Encryption);
+    let step = CommandStep::Continue(eact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_pin(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    if !desc.arg.text.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let pact = IambAction::from(MessageAction::Pin);
+    let step = CommandStep::Continue(pact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_unpin(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    if !desc.arg.text.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let pact = IambAction::from(MessageAction::Unpin);
+    let step = CommandStep::Continue(pact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
 fn iamb_editor(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     if !desc.arg.text.is_empty() {
         return Result::Err(CommandError::InvalidArgument);
@@ -366,10 +610,75 @@ fn iamb_join(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     return Ok(step);
 }
 
+fn iamb_ignore(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.len() != 1 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let ignore = IambAction::from(HomeserverAction::Ignore(args.remove(0)));
+    let step = CommandStep::Continue(ignore.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_unignore(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.len() != 1 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let unignore = IambAction::from(HomeserverAction::Unignore(args.remove(0)));
+    let step = CommandStep::Continue(unignore.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_ignored(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    if !desc.arg.text.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let ignored = IambAction::from(HomeserverAction::ListIgnored);
+    let step = CommandStep::Continue(ignored.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_knock(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.is_empty() || args.len() > 2 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let room = args.remove(0);
+    let reason = args.pop();
+
+    let knock = IambAction::from(HomeserverAction::Knock(room, reason));
+    let step = CommandStep::Continue(knock.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_successor(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    if !desc.arg.text.is_empty() {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let join = IambAction::Room(RoomAction::JoinSuccessor);
+    let step = CommandStep::Continue(join.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
 fn iamb_create(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     let args = desc.arg.options()?;
     let mut flags = CreateRoomFlags::NONE;
     let mut alias = None;
+    let mut parent = None;
     let mut ct = CreateRoomType::Room;
 
     for arg in args {
@@ -386,6 +695,16 @@ fn iamb_create(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
                             alias = Some(arg);
                         }
                     },
+                    "parent" => {
+                        if parent.is_some() {
+                            let msg = "Multiple ++parent arguments are not allowed";
+                            let err = CommandError::Error(msg.into());
+
+                            return Err(err);
+                        } else {
+                            parent = Some(arg);
+                        }
+                    },
                     _ => return Err(CommandError::InvalidArgument),
                 }
             },
@@ -406,72 +725,287 @@ fn iamb_create(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
         }
     }
 
-    let hact = HomeserverAction::CreateRoom(alias, ct, flags);
+    let hact = HomeserverAction::CreateRoom(alias, ct, flags, parent);
     let iact = IambAction::from(hact);
     let step = CommandStep::Continue(iact.into(), ctx.context.clone());
 
     return Ok(step);
 }
 
-fn iamb_room(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
-    let mut args = desc.arg.strings()?;
+fn iamb_search(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let args = desc.arg.options()?;
+    let mut flags = SearchFlags::NONE;
+    let mut terms = vec![];
 
-    if args.len() < 2 {
-        return Result::Err(CommandError::InvalidArgument);
+    for arg in args {
+        match arg {
+            OptionType::Flag(name, None) => {
+                match name.as_str() {
+                    "all" => flags |= SearchFlags::ALL,
+                    "local" => flags |= SearchFlags::LOCAL,
+                    _ => return Err(CommandError::InvalidArgument),
+                }
+            },
+            OptionType::Flag(_, Some(_)) => return Err(CommandError::InvalidArgument),
+            OptionType::Positional(term) => terms.push(term),
+        }
     }
 
-    let field = args.remove(0);
-    let action = args.remove(0);
-
-    if args.len() > 1 {
+    if terms.is_empty() {
         return Result::Err(CommandError::InvalidArgument);
     }
 
-    let act: IambAction = match (field.as_str(), action.as_str(), args.pop()) {
-        // :room dm set
-        ("dm", "set", None) => RoomAction::SetDirect(true).into(),
-        ("dm", "set", Some(_)) => return Result::Err(CommandError::InvalidArgument),
-
-        // :room dm set
-        ("dm", "unset", None) => RoomAction::SetDirect(false).into(),
-        ("dm", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
-
-        // :room [kick|ban|unban] <user>
-        ("kick", u, r) => {
-            RoomAction::MemberUpdate(MemberUpdateAction::Kick, u.into(), r, desc.bang).into()
-        },
-        ("ban", u, r) => {
-            RoomAction::MemberUpdate(MemberUpdateAction::Ban, u.into(), r, desc.bang).into()
-        },
-        ("unban", u, r) => {
-            RoomAction::MemberUpdate(MemberUpdateAction::Unban, u.into(), r, desc.bang).into()
-        },
+    let iact = IambAction::Search(terms.join(" "), flags);
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
 
-        // :room history set <visibility>
-        ("history", "set", Some(s)) => RoomAction::Set(RoomField::History, s).into(),
-        ("history", "set", None) => return Result::Err(CommandError::InvalidArgument),
+    return Ok(step);
+}
 
-        // :room history unset
-        ("history", "unset", None) => RoomAction::Unset(RoomField::History).into(),
-        ("history", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+fn iamb_directory(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let args = desc.arg.options()?;
+    let mut server = None;
+    let mut more = false;
+    let mut terms = vec![];
 
-        // :room history show
-        ("history", "show", None) => RoomAction::Show(RoomField::History).into(),
-        ("history", "show", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+    for arg in args {
+        match arg {
+            OptionType::Flag(name, Some(arg)) => {
+                match name.as_str() {
+                    "server" => {
+                        if server.is_some() {
+                            let msg = "Multiple ++server arguments are not allowed";
+                            let err = CommandError::Error(msg.into());
 
-        // :room name set <room-name>
-        ("name", "set", Some(s)) => RoomAction::Set(RoomField::Name, s).into(),
-        ("name", "set", None) => return Result::Err(CommandError::InvalidArgument),
+                            return Err(err);
+                        }
 
-        // :room name unset
-        ("name", "unset", None) => RoomAction::Unset(RoomField::Name).into(),
-        ("name", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+                        let Ok(name) = OwnedServerName::try_from(arg.as_str()) else {
+                            let msg = format!("Invalid server name: {arg}");
+                            let err = CommandError::Error(msg);
 
-        // :room topic set <topic>
-        ("topic", "set", Some(s)) => RoomAction::Set(RoomField::Topic, s).into(),
-        ("topic", "set", None) => return Result::Err(CommandError::InvalidArgument),
+                            return Err(err);
+                        };
 
-        // :room topic unset
+                        server = Some(name);
+                    },
+                    _ => return Err(CommandError::InvalidArgument),
+                }
+            },
+            OptionType::Flag(name, None) => {
+                match name.as_str() {
+                    "more" => more = true,
+                    _ => return Err(CommandError::InvalidArgument),
+                }
+            },
+            OptionType::Positional(term) => terms.push(term),
+        }
+    }
+
+    let act = if more {
+        if server.is_some() || !terms.is_empty() {
+            let msg = "++more doesn't take any other arguments";
+            let err = CommandError::Error(msg.into());
+
+            return Err(err);
+        }
+
+        DirectoryAction::NextPage
+    } else {
+        let filter = (!terms.is_empty()).then(|| terms.join(" "));
+
+        DirectoryAction::Search(server, filter)
+    };
+
+    let iact = IambAction::from(act);
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_space(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.len() != 3 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let room = args.pop().unwrap_or_default();
+    let action = args.pop().unwrap_or_default();
+    let field = args.pop().unwrap_or_default();
+
+    let act = match (field.as_str(), action.as_str()) {
+        // :space child add <room>
+        ("child", "add") => RoomAction::SpaceChildAdd(room),
+
+        // :space child remove <room>
+        ("child", "remove") => RoomAction::SpaceChildRemove(room),
+
+        // :space parent set <space>
+        ("parent", "set") => RoomAction::SpaceParentSet(room),
+
+        _ => return Result::Err(CommandError::InvalidArgument),
+    };
+
+    let iact = IambAction::from(act);
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_room(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.len() < 2 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let field = args.remove(0);
+    let action = args.remove(0);
+
+    // :room power set <user|event-type> <level>
+    if let ("power", "set", [_, _]) = (field.as_str(), action.as_str(), args.as_slice()) {
+        let level = args.pop().unwrap_or_default();
+        let target = args.pop().unwrap_or_default();
+        let act = IambAction::from(RoomAction::Set(power_field(target)?, level));
+        let step = CommandStep::Continue(act.into(), ctx.context.clone());
+
+        return Ok(step);
+    }
+
+    // :room joinrule set <rule> [space...]
+    if let ("joinrule", "set", [_, _, ..]) = (field.as_str(), action.as_str(), args.as_slice()) {
+        let act = IambAction::from(RoomAction::Set(RoomField::JoinRule, args.join(" ")));
+        let step = CommandStep::Continue(act.into(), ctx.context.clone());
+
+        return Ok(step);
+    }
+
+    // :room purge <user> [count|since] [reason]
+    if field == "purge" {
+        let limit = match args.first().and_then(|arg| purge_limit(arg)) {
+            Some(limit) => {
+                args.remove(0);
+                limit
+            },
+            None => PurgeLimit::Count(PURGE_DEFAULT_COUNT),
+        };
+
+        if args.len() > 1 {
+            return Result::Err(CommandError::InvalidArgument);
+        }
+
+        let act = IambAction::from(RoomAction::Purge(action, limit, args.pop(), desc.bang));
+        let step = CommandStep::Continue(act.into(), ctx.context.clone());
+
+        return Ok(step);
+    }
+
+    // :room knock [accept|deny] <user> [reason]
+    if field == "knock" {
+        let mua = match action.as_str() {
+            "accept" => MemberUpdateAction::KnockAccept,
+            "deny" => MemberUpdateAction::KnockDeny,
+            _ => return Result::Err(CommandError::InvalidArgument),
+        };
+
+        if args.is_empty() || args.len() > 2 {
+            return Result::Err(CommandError::InvalidArgument);
+        }
+
+        let reason = if args.len() == 2 { args.pop() } else { None };
+        let user = args.pop().unwrap_or_default();
+        let act = IambAction::from(RoomAction::MemberUpdate(mua, user, reason, desc.bang));
+        let step = CommandStep::Continue(act.into(), ctx.context.clone());
+
+        return Ok(step);
+    }
+
+    if args.len() > 1 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let act: IambAction = match (field.as_str(), action.as_str(), args.pop()) {
+        // :room dm set
+        ("dm", "set", None) => RoomAction::SetDirect(true).into(),
+        ("dm", "set", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room dm set
+        ("dm", "unset", None) => RoomAction::SetDirect(false).into(),
+        ("dm", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room [kick|ban|unban] <user>
+        ("kick", u, r) => {
+            RoomAction::MemberUpdate(MemberUpdateAction::Kick, u.into(), r, desc.bang).into()
+        },
+        ("ban", u, r) => {
+            RoomAction::MemberUpdate(MemberUpdateAction::Ban, u.into(), r, desc.bang).into()
+        },
+        ("unban", u, r) => {
+            RoomAction::MemberUpdate(MemberUpdateAction::Unban, u.into(), r, desc.bang).into()
+        },
+
+        // :room joinrule set <rule>
+        ("joinrule", "set", Some(s)) => RoomAction::Set(RoomField::JoinRule, s).into(),
+        ("joinrule", "set", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room joinrule show
+        ("joinrule", "show", None) => RoomAction::Show(RoomField::JoinRule).into(),
+        ("joinrule", "show", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room acl [allow|deny] <server-glob>
+        ("acl", "allow", Some(s)) => {
+            RoomAction::ServerAcl(ServerAclAction::Allow, s, desc.bang).into()
+        },
+        ("acl", "deny", Some(s)) => {
+            RoomAction::ServerAcl(ServerAclAction::Deny, s, desc.bang).into()
+        },
+        ("acl", "allow" | "deny", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room acl show
+        ("acl", "show", None) => RoomAction::Show(RoomField::ServerAcl).into(),
+        ("acl", "show", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room encryption enable
+        ("encryption", "enable", None) => RoomAction::EnableEncryption(desc.bang).into(),
+        ("encryption", "enable", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room nick set <display-name>
+        ("nick", "set", Some(s)) => RoomAction::Set(RoomField::Nick, s).into(),
+        ("nick", "set", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room nick unset
+        ("nick", "unset", None) => RoomAction::Unset(RoomField::Nick).into(),
+        ("nick", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room nick show
+        ("nick", "show", None) => RoomAction::Show(RoomField::Nick).into(),
+        ("nick", "show", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room history set <visibility>
+        ("history", "set", Some(s)) => RoomAction::Set(RoomField::History, s).into(),
+        ("history", "set", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room history unset
+        ("history", "unset", None) => RoomAction::Unset(RoomField::History).into(),
+        ("history", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room history show
+        ("history", "show", None) => RoomAction::Show(RoomField::History).into(),
+        ("history", "show", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room name set <room-name>
+        ("name", "set", Some(s)) => RoomAction::Set(RoomField::Name, s).into(),
+        ("name", "set", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room name unset
+        ("name", "unset", None) => RoomAction::Unset(RoomField::Name).into(),
+        ("name", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room topic set <topic>
+        ("topic", "set", Some(s)) => RoomAction::Set(RoomField::Topic, s).into(),
+        ("topic", "set", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room topic unset
         ("topic", "unset", None) => RoomAction::Unset(RoomField::Topic).into(),
         ("topic", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
 
@@ -531,6 +1065,68 @@ fn iamb_room(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
             return Result::Err(CommandError::InvalidArgument)
         },
 
+        // :room power set <user|event-type> <level>
+        ("power", "set", _) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room power unset <user|event-type>
+        ("power", "unset", Some(s)) => RoomAction::Unset(power_field(s)?).into(),
+        ("power", "unset", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :room power show [user|event-type]
+        ("power", "show", Some(s)) => RoomAction::Show(power_field(s)?).into(),
+        ("power", "show", None) => RoomAction::Show(RoomField::PowerLevels).into(),
+
+        _ => return Result::Err(CommandError::InvalidArgument),
+    };
+
+    let step = CommandStep::Continue(act.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_presence(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.is_empty() || args.len() > 2 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let presence = match args.remove(0).as_str() {
+        "online" => PresenceState::Online,
+        "unavailable" | "away" => PresenceState::Unavailable,
+        "offline" => PresenceState::Offline,
+        _ => return Result::Err(CommandError::InvalidArgument),
+    };
+
+    let act = IambAction::from(HomeserverAction::Presence(presence, args.pop()));
+    let step = CommandStep::Continue(act.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_profile(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?;
+
+    if args.len() < 2 || args.len() > 3 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let field = args.remove(0);
+    let action = args.remove(0);
+
+    let act: IambAction = match (field.as_str(), action.as_str(), args.pop()) {
+        // :profile name set <display-name>
+        ("name", "set", Some(s)) => ProfileAction::SetName(s).into(),
+        ("name", "set", None) => return Result::Err(CommandError::InvalidArgument),
+
+        // :profile name unset
+        ("name", "unset", None) => ProfileAction::UnsetName.into(),
+        ("name", "unset", Some(_)) => return Result::Err(CommandError::InvalidArgument),
+
+        // :profile avatar set <file>
+        ("avatar", "set", Some(s)) => ProfileAction::SetAvatar(s).into(),
+        ("avatar", "set", None) => return Result::Err(CommandError::InvalidArgument),
+
         _ => return Result::Err(CommandError::InvalidArgument),
     };
 
@@ -605,7 +1201,68 @@ fn iamb_logout(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
     return Ok(step);
 }
 
+fn iamb_state(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut args = desc.arg.strings()?.into_iter();
+
+    let Some(action) = args.next() else {
+        return Result::Err(CommandError::InvalidArgument);
+    };
+
+    let ract = match (action.as_str(), args.next(), args.next(), args.next()) {
+        // :state show [type] [state_key]
+        ("show", ty, key, None) => RoomAction::StateShow(ty, key),
+
+        // :state edit <type> [state_key]
+        ("edit", Some(ty), key, None) => RoomAction::StateEdit(ty, key.unwrap_or_default()),
+
+        _ => return Result::Err(CommandError::InvalidArgument),
+    };
+
+    let iact = IambAction::from(ract);
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
+fn iamb_accountdata(desc: CommandDescription, ctx: &mut ProgContext) -> ProgResult {
+    let mut room = false;
+    let mut args = vec![];
+
+    for arg in desc.arg.options()? {
+        match arg {
+            OptionType::Flag(name, None) if name == "room" => room = true,
+            OptionType::Flag(_, _) => return Err(CommandError::InvalidArgument),
+            OptionType::Positional(arg) => args.push(arg),
+        }
+    }
+
+    if args.len() != 2 {
+        return Result::Err(CommandError::InvalidArgument);
+    }
+
+    let ty = args.pop().unwrap_or_default();
+    let act = match args.pop().unwrap_or_default().as_str() {
+        "show" => RawJsonAction::Show(ty),
+        "edit" => RawJsonAction::Edit(ty),
+        _ => return Result::Err(CommandError::InvalidArgument),
+    };
+
+    let iact = if room {
+        IambAction::from(RoomAction::AccountData(act))
+    } else {
+        IambAction::from(HomeserverAction::AccountData(act))
+    };
+    let step = CommandStep::Continue(iact.into(), ctx.context.clone());
+
+    return Ok(step);
+}
+
 fn add_iamb_commands(cmds: &mut ProgramCommands) {
+    cmds.add_command(ProgramCommand {
+        name: "accountdata".into(),
+        aliases: vec![],
+        f: iamb_accountdata,
+    });
     cmds.add_command(ProgramCommand {
         name: "cancel".into(),
         aliases: vec![],
@@ -622,6 +1279,16 @@ fn add_iamb_commands(cmds: &mut ProgramCommands) {
         f: iamb_chats,
     });
     cmds.add_command(ProgramCommand { name: "dms".into(), aliases: vec![], f: iamb_dms });
+    cmds.add_command(ProgramCommand {
+        name: "devices".into(),
+        aliases: vec![],
+        f: iamb_devices,
+    });
+    cmds.add_command(ProgramCommand {
+        name: "directory".into(),
+        aliases: vec![],
+        f: iamb_directory,
+    });
     cmds.add_command(ProgramCommand {
         name: "download".into(),
         aliases: vec![],
@@ -629,13 +1296,33 @@ fn add_iamb_commands(cmds: &mut ProgramCommands) {
     });
     cmds.add_command(ProgramCommand { name: "open".into(), aliases: vec![], f: iamb_open });
     cmds.add_command(ProgramCommand { name: "edit".into(), aliases: vec![], f: iamb_edit });
+    cmds.add_command(ProgramCommand {
+        name: "encryption".into(),
+        aliases: vec![],
+        f: iamb_encryption,
+    });
     cmds.add_command(ProgramCommand {
         name: "invite".into(),
         aliases: vec![],
         f: iamb_invite,
     });
+    cmds.add_command(ProgramCommand {
+        name: "ignore".into(),
+        aliases: vec![],
+        f: iamb_ignore,
+    });
+    cmds.add_command(ProgramCommand {
+        name: "ignored".into(),
+        aliases: vec![],
+        f: iamb_ignored,
+    });
     cmds.add_command(ProgramCommand { name: "join".into(), aliases: vec![], f: iamb_join });
     cmds.add_command(ProgramCommand { name: "keys".into(), aliases: vec![], f: iamb_keys });
+    cmds.add_command(ProgramCommand {
+        name: "knock".into(),
+        aliases: vec![],
+        f: iamb_knock,
+    });
     cmds.add_command(ProgramCommand {
         name: "leave".into(),
         aliases: vec![],
@@ -646,6 +1333,19 @@ fn add_iamb_commands(cmds: &mut ProgramCommands) {
         aliases: vec![],
         f: iamb_members,
     });
+    cmds.add_command(ProgramCommand { name: "pin".into(), aliases: vec![], f: iamb_pin });
+    cmds.add_command(ProgramCommand { name: "poll".into(), aliases: vec![], f: iamb_poll });
+    cmds.add_command(ProgramCommand { name: "pins".into(), aliases: vec![], f: iamb_pins });
+    cmds.add_command(ProgramCommand {
+        name: "presence".into(),
+        aliases: vec![],
+        f: iamb_presence,
+    });
+    cmds.add_command(ProgramCommand {
+        name: "profile".into(),
+        aliases: vec![],
+        f: iamb_profile,
+    });
     cmds.add_command(ProgramCommand {
         name: "react".into(),
         aliases: vec![],
@@ -667,11 +1367,41 @@ fn add_iamb_commands(cmds: &mut ProgramCommands) {
         f: iamb_rooms,
     });
     cmds.add_command(ProgramCommand { name: "room".into(), aliases: vec![], f: iamb_room });
+    cmds.add_command(ProgramCommand {
+        name: "search".into(),
+        aliases: vec![],
+        f: iamb_search,
+    });
+    cmds.add_command(ProgramCommand {
+        name: "source".into(),
+        aliases: vec![],
+        f: iamb_source,
+    });
+    cmds.add_command(ProgramCommand {
+        name: "space".into(),
+        aliases: vec![],
+        f: iamb_space,
+    });
     cmds.add_command(ProgramCommand {
         name: "spaces".into(),
         aliases: vec![],
         f: iamb_spaces,
     });
+    cmds.add_command(ProgramCommand {
+        name: "state".into(),
+        aliases: vec![],
+        f: iamb_state,
+    });
+    cmds.add_command(ProgramCommand {
+        name: "successor".into(),
+        aliases: vec![],
+        f: iamb_successor,
+    });
+    cmds.add_command(ProgramCommand {
+        name: "unignore".into(),
+        aliases: vec![],
+        f: iamb_unignore,
+    });
     cmds.add_command(ProgramCommand {
         name: "unreads".into(),
         aliases: vec![],
@@ -682,6 +1412,11 @@ fn add_iamb_commands(cmds: &mut ProgramCommands) {
         aliases: vec![],
         f: iamb_unreact,
     });
+    cmds.add_command(ProgramCommand {
+        name: "unpin".into(),
+        aliases: vec![],
+        f: iamb_unpin,
+    });
     cmds.add_command(ProgramCommand {
         name: "upload".into(),
         aliases: vec![],
@@ -692,6 +1427,11 @@ fn add_iamb_commands(cmds: &mut ProgramCommands) {
         aliases: vec![],
         f: iamb_verify,
     });
+    cmds.add_command(ProgramCommand {
+        name: "whois".into(),
+        aliases: vec![],
+        f: iamb_whois,
+    });
     cmds.add_command(ProgramCommand {
         name: "welcome".into(),
         aliases: vec![],
@@ -770,10 +1510,36 @@ mod tests {
 
         let res = cmds.input_cmd(":verify mismatch a b c d e f", ctx.clone());
         assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd(":verify withdraw @user5:example.com", ctx.clone()).unwrap();
+        let user = OwnedUserId::try_from("@user5:example.com").unwrap();
+        let act = IambAction::from(IdentityAction::Withdraw(user));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd(":verify withdraw user5", ctx.clone());
+        let msg = "Invalid user identifier: user5".to_string();
+        assert_eq!(res, Err(CommandError::Error(msg)));
     }
 
     #[test]
-    fn test_cmd_join() {
+    fn test_cmd_whois() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd(":whois @user1:example.com", ctx.clone()).unwrap();
+        let user = OwnedUserId::try_from("@user1:example.com").unwrap();
+        let act = IambAction::from(IdentityAction::Show(user));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd(":whois", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd(":whois @user1:example.com @user2:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_join() {
         let mut cmds = setup_commands();
         let ctx = EditContext::default();
 
@@ -792,6 +1558,19 @@ mod tests {
         assert_eq!(res, Err(CommandError::InvalidArgument));
     }
 
+    #[test]
+    fn test_cmd_successor() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("successor", ctx.clone()).unwrap();
+        let act = IambAction::Room(RoomAction::JoinSuccessor);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("successor #foobar:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
     #[test]
     fn test_cmd_room_invalid() {
         let mut cmds = setup_commands();
@@ -1063,6 +1842,52 @@ mod tests {
         assert_eq!(res, vec![(act.into(), ctx.clone())]);
     }
 
+    #[test]
+    fn test_cmd_room_power() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("room power set @user:example.com 50", ctx.clone()).unwrap();
+        let field = RoomField::PowerLevel(user_id!("@user:example.com").to_owned());
+        let act = RoomAction::Set(field, "50".into());
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room power set m.room.name mod", ctx.clone()).unwrap();
+        let act = RoomAction::Set(RoomField::EventPowerLevel("m.room.name".into()), "mod".into());
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room power unset @user:example.com", ctx.clone()).unwrap();
+        let field = RoomField::PowerLevel(user_id!("@user:example.com").to_owned());
+        let act = RoomAction::Unset(field);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room power unset m.reaction", ctx.clone()).unwrap();
+        let act = RoomAction::Unset(RoomField::EventPowerLevel("m.reaction".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room power show", ctx.clone()).unwrap();
+        let act = RoomAction::Show(RoomField::PowerLevels);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room power show @user:example.com", ctx.clone()).unwrap();
+        let field = RoomField::PowerLevel(user_id!("@user:example.com").to_owned());
+        let act = RoomAction::Show(field);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room power set @user:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room power set @user:example.com 50 100", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room power unset", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room power set @invalid 50", ctx.clone());
+        let msg = "Invalid user identifier: @invalid";
+        assert_eq!(res, Err(CommandError::Error(msg.into())));
+    }
+
     #[test]
     fn test_cmd_invite() {
         let mut cmds = setup_commands();
@@ -1135,6 +1960,283 @@ mod tests {
         assert_eq!(res, vec![(act.into(), ctx.clone())]);
     }
 
+    #[test]
+    fn test_cmd_room_joinrule() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("room joinrule set knock", ctx.clone()).unwrap();
+        let act = RoomAction::Set(RoomField::JoinRule, "knock".into());
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("room joinrule set restricted #a:example.com !b:example.com", ctx.clone())
+            .unwrap();
+        let rule = "restricted #a:example.com !b:example.com".into();
+        let act = RoomAction::Set(RoomField::JoinRule, rule);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room joinrule show", ctx.clone()).unwrap();
+        let act = RoomAction::Show(RoomField::JoinRule);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        // Invalid invocations.
+        let res = cmds.input_cmd("room joinrule set", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room joinrule show public", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room joinrule unset", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_room_acl() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("room acl allow *.example.com", ctx.clone()).unwrap();
+        let act = RoomAction::ServerAcl(ServerAclAction::Allow, "*.example.com".into(), false);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room! acl deny evil.example.com", ctx.clone()).unwrap();
+        let act = RoomAction::ServerAcl(ServerAclAction::Deny, "evil.example.com".into(), true);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room acl show", ctx.clone()).unwrap();
+        let act = RoomAction::Show(RoomField::ServerAcl);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        // Invalid invocations.
+        let res = cmds.input_cmd("room acl deny", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room acl show example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room acl deny a.example.com b.example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_room_encryption() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("room encryption enable", ctx.clone()).unwrap();
+        let act = RoomAction::EnableEncryption(false);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room! encryption enable", ctx.clone()).unwrap();
+        let act = RoomAction::EnableEncryption(true);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room encryption enable megolm", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room encryption disable", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_room_purge() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("room purge @spam:example.com", ctx.clone()).unwrap();
+        let limit = PurgeLimit::Count(PURGE_DEFAULT_COUNT);
+        let act = RoomAction::Purge("@spam:example.com".into(), limit, None, false);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room! purge @spam:example.com 20", ctx.clone()).unwrap();
+        let act = RoomAction::Purge("@spam:example.com".into(), PurgeLimit::Count(20), None, true);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("room purge @spam:example.com 2h \"spam\"", ctx.clone())
+            .unwrap();
+        let limit = PurgeLimit::Since(Duration::from_secs(7200));
+        let act = RoomAction::Purge("@spam:example.com".into(), limit, Some("spam".into()), false);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room purge @spam:example.com spam", ctx.clone()).unwrap();
+        let limit = PurgeLimit::Count(PURGE_DEFAULT_COUNT);
+        let act = RoomAction::Purge("@spam:example.com".into(), limit, Some("spam".into()), false);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room purge @spam:example.com 1d spam more", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room purge", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_room_knock() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("room knock accept @user:example.com", ctx.clone()).unwrap();
+        let act = IambAction::Room(RoomAction::MemberUpdate(
+            MemberUpdateAction::KnockAccept,
+            "@user:example.com".into(),
+            None,
+            false,
+        ));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("room! knock deny @user:example.com \"not today\"", ctx.clone())
+            .unwrap();
+        let act = IambAction::Room(RoomAction::MemberUpdate(
+            MemberUpdateAction::KnockDeny,
+            "@user:example.com".into(),
+            Some("not today".into()),
+            true,
+        ));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        // Invalid invocations.
+        let res = cmds.input_cmd("room knock accept", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room knock ignore @user:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("room knock deny @user:example.com a b", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_knock() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("knock #room:example.com", ctx.clone()).unwrap();
+        let act = HomeserverAction::Knock("#room:example.com".into(), None);
+        let act = IambAction::from(act);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("knock #room:example.com \"let me in\"", ctx.clone())
+            .unwrap();
+        let act = HomeserverAction::Knock("#room:example.com".into(), Some("let me in".into()));
+        let act = IambAction::from(act);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("knock", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("knock #room:example.com a b", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_ignore() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("ignore @troll:example.com", ctx.clone()).unwrap();
+        let act = IambAction::from(HomeserverAction::Ignore("@troll:example.com".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("unignore @troll:example.com", ctx.clone()).unwrap();
+        let act = IambAction::from(HomeserverAction::Unignore("@troll:example.com".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("ignored", ctx.clone()).unwrap();
+        let act = IambAction::from(HomeserverAction::ListIgnored);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("ignore", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("unignore @a:example.com @b:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("ignored @troll:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_presence() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("presence online", ctx.clone()).unwrap();
+        let act = HomeserverAction::Presence(PresenceState::Online, None);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("presence unavailable \"out for lunch\"", ctx.clone())
+            .unwrap();
+        let act =
+            HomeserverAction::Presence(PresenceState::Unavailable, Some("out for lunch".into()));
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("presence offline", ctx.clone()).unwrap();
+        let act = HomeserverAction::Presence(PresenceState::Offline, None);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("presence", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("presence busy", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("presence online a b", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_profile() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("profile name set \"Jane Doe\"", ctx.clone()).unwrap();
+        let act = IambAction::from(ProfileAction::SetName("Jane Doe".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("profile name unset", ctx.clone()).unwrap();
+        let act = IambAction::from(ProfileAction::UnsetName);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("profile avatar set ~/avatar.png", ctx.clone()).unwrap();
+        let act = IambAction::from(ProfileAction::SetAvatar("~/avatar.png".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("profile name set", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("profile name unset Jane", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("profile avatar unset", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_room_nick() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("room nick set \"Jane (work)\"", ctx.clone()).unwrap();
+        let act = RoomAction::Set(RoomField::Nick, "Jane (work)".into());
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room nick unset", ctx.clone()).unwrap();
+        let act = RoomAction::Unset(RoomField::Nick);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room nick show", ctx.clone()).unwrap();
+        let act = RoomAction::Show(RoomField::Nick);
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("room nick set", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
     #[test]
     fn test_cmd_room_ban_unban() {
         let mut cmds = setup_commands();
@@ -1188,6 +2290,202 @@ mod tests {
         assert_eq!(res, Err(CommandError::InvalidArgument));
     }
 
+    #[test]
+    fn test_cmd_pin_unpin() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("pin", ctx.clone()).unwrap();
+        let act = IambAction::Message(MessageAction::Pin);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("unpin", ctx.clone()).unwrap();
+        let act = IambAction::Message(MessageAction::Unpin);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("pin foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("unpin foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("pins foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_poll() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds
+            .input_cmd("poll create \"Lunch?\" \"Tacos\" \"Ramen\"", ctx.clone())
+            .unwrap();
+        let answers = vec!["Tacos".into(), "Ramen".into()];
+        let act = IambAction::from(SendAction::Poll("Lunch?".into(), answers));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("poll vote 2", ctx.clone()).unwrap();
+        let act = IambAction::Message(MessageAction::PollVote(2));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("poll end", ctx.clone()).unwrap();
+        let act = IambAction::Message(MessageAction::PollEnd);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("poll vote two", ctx.clone());
+        let msg = "Invalid answer number: two".to_string();
+        assert_eq!(res, Err(CommandError::Error(msg)));
+
+        let res = cmds.input_cmd("poll create \"Lunch?\"", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("poll vote", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("poll end now", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("poll", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_state() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("state show", ctx.clone()).unwrap();
+        let act = RoomAction::StateShow(None, None);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("state show m.room.member", ctx.clone()).unwrap();
+        let act = RoomAction::StateShow(Some("m.room.member".into()), None);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("state show m.room.member @user:example.com", ctx.clone());
+        let act =
+            RoomAction::StateShow(Some("m.room.member".into()), Some("@user:example.com".into()));
+        assert_eq!(res, Ok(vec![(act.into(), ctx.clone())]));
+
+        let res = cmds.input_cmd("state edit m.room.topic", ctx.clone()).unwrap();
+        let act = RoomAction::StateEdit("m.room.topic".into(), "".into());
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("state edit m.space.child !foo:example.com", ctx.clone());
+        let act = RoomAction::StateEdit("m.space.child".into(), "!foo:example.com".into());
+        assert_eq!(res, Ok(vec![(act.into(), ctx.clone())]));
+
+        let res = cmds.input_cmd("state", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("state edit", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("state show a b c", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("state foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_accountdata() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("accountdata show m.direct", ctx.clone()).unwrap();
+        let act = HomeserverAction::AccountData(RawJsonAction::Show("m.direct".into()));
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("accountdata edit m.direct", ctx.clone()).unwrap();
+        let act = HomeserverAction::AccountData(RawJsonAction::Edit("m.direct".into()));
+        assert_eq!(res, vec![(IambAction::from(act).into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("accountdata show m.tag ++room", ctx.clone()).unwrap();
+        let act = RoomAction::AccountData(RawJsonAction::Show("m.tag".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("accountdata ++room edit m.tag", ctx.clone()).unwrap();
+        let act = RoomAction::AccountData(RawJsonAction::Edit("m.tag".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("accountdata show", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("accountdata show m.tag ++foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("accountdata delete m.tag", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_devices() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("devices", ctx.clone()).unwrap();
+        let act = IambAction::from(DevicesAction::List);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("devices rename ABCDEFGH \"Old laptop\"", ctx.clone())
+            .unwrap();
+        let act = IambAction::from(DevicesAction::Rename("ABCDEFGH".into(), "Old laptop".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("devices delete ABCDEFGH", ctx.clone()).unwrap();
+        let act = IambAction::from(DevicesAction::Delete("ABCDEFGH".into(), false));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("devices! delete ABCDEFGH", ctx.clone()).unwrap();
+        let act = IambAction::from(DevicesAction::Delete("ABCDEFGH".into(), true));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("devices verify ABCDEFGH", ctx.clone()).unwrap();
+        let act = IambAction::from(DevicesAction::Verify("ABCDEFGH".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("devices rename ABCDEFGH", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("devices delete", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("devices verify ABCDEFGH foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("devices foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_source() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("source", ctx.clone()).unwrap();
+        let act = IambAction::Message(MessageAction::Source);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("source foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_encryption() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("encryption", ctx.clone()).unwrap();
+        let act = IambAction::Message(MessageAction::Encryption);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("encryption foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
     #[test]
     fn test_cmd_keys() {
         let mut cmds = setup_commands();
@@ -1213,5 +2511,188 @@ mod tests {
 
         let res = cmds.input_cmd("keys import foo bar baz", ctx.clone());
         assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("keys request", ctx.clone()).unwrap();
+        let act = IambAction::Message(MessageAction::DownloadBackupKey);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("keys request foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_keys_backup() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("keys backup enable", ctx.clone()).unwrap();
+        let act = IambAction::Keys(KeysAction::BackupEnable(None));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("keys backup enable pword", ctx.clone()).unwrap();
+        let act = IambAction::Keys(KeysAction::BackupEnable(Some("pword".into())));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("keys backup status", ctx.clone()).unwrap();
+        let act = IambAction::Keys(KeysAction::BackupStatus);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("keys backup restore EsTc abcd 1234", ctx.clone()).unwrap();
+        let act = IambAction::Keys(KeysAction::BackupRestore("EsTc abcd 1234".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("keys backup restore \"a passphrase\"", ctx.clone())
+            .unwrap();
+        let act = IambAction::Keys(KeysAction::BackupRestore("a passphrase".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        // Invalid invocations.
+        let res = cmds.input_cmd("keys backup", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("keys backup restore", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("keys backup status foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("keys backup enable foo bar", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("keys backup foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_search() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("search hello", ctx.clone()).unwrap();
+        let act = IambAction::Search("hello".into(), SearchFlags::NONE);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("search hello world", ctx.clone()).unwrap();
+        let act = IambAction::Search("hello world".into(), SearchFlags::NONE);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("search ++all hello", ctx.clone()).unwrap();
+        let act = IambAction::Search("hello".into(), SearchFlags::ALL);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("search ++local hello", ctx.clone()).unwrap();
+        let act = IambAction::Search("hello".into(), SearchFlags::LOCAL);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("search ++all ++local hello", ctx.clone()).unwrap();
+        let act = IambAction::Search("hello".into(), SearchFlags::ALL | SearchFlags::LOCAL);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        // Invalid invocations.
+        let res = cmds.input_cmd("search", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("search ++all", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("search ++foo hello", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_create() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("create", ctx.clone()).unwrap();
+        let flags = CreateRoomFlags::NONE;
+        let act = HomeserverAction::CreateRoom(None, CreateRoomType::Room, flags, None);
+        let act = IambAction::from(act);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("create ++alias=foo ++parent=#space:example.com", ctx.clone())
+            .unwrap();
+        let act = HomeserverAction::CreateRoom(
+            Some("foo".into()),
+            CreateRoomType::Room,
+            CreateRoomFlags::NONE,
+            Some("#space:example.com".into()),
+        );
+        let act = IambAction::from(act);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res =
+            cmds.input_cmd("create ++parent=#a:example.com ++parent=#b:example.com", ctx.clone());
+        let msg = "Multiple ++parent arguments are not allowed";
+        assert_eq!(res, Err(CommandError::Error(msg.into())));
+    }
+
+    #[test]
+    fn test_cmd_space() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("space child add #room:example.com", ctx.clone()).unwrap();
+        let act = IambAction::from(RoomAction::SpaceChildAdd("#room:example.com".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds
+            .input_cmd("space child remove !room:example.com", ctx.clone())
+            .unwrap();
+        let act = IambAction::from(RoomAction::SpaceChildRemove("!room:example.com".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("space parent set #space:example.com", ctx.clone()).unwrap();
+        let act = IambAction::from(RoomAction::SpaceParentSet("#space:example.com".into()));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        // Invalid invocations.
+        let res = cmds.input_cmd("space child add", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("space child set #room:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+
+        let res = cmds.input_cmd("space parent set #a:example.com #b:example.com", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
+    }
+
+    #[test]
+    fn test_cmd_directory() {
+        let mut cmds = setup_commands();
+        let ctx = EditContext::default();
+
+        let res = cmds.input_cmd("directory", ctx.clone()).unwrap();
+        let act = IambAction::from(DirectoryAction::Search(None, None));
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("directory rust programming", ctx.clone()).unwrap();
+        let act = DirectoryAction::Search(None, Some("rust programming".into()));
+        let act = IambAction::from(act);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("directory ++server=matrix.org rust", ctx.clone()).unwrap();
+        let server = OwnedServerName::try_from("matrix.org").unwrap();
+        let act = DirectoryAction::Search(Some(server), Some("rust".into()));
+        let act = IambAction::from(act);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        let res = cmds.input_cmd("directory ++more", ctx.clone()).unwrap();
+        let act = IambAction::from(DirectoryAction::NextPage);
+        assert_eq!(res, vec![(act.into(), ctx.clone())]);
+
+        // Invalid invocations.
+        let res = cmds.input_cmd("directory ++server=bad/name", ctx.clone());
+        let msg = "Invalid server name: bad/name";
+        assert_eq!(res, Err(CommandError::Error(msg.into())));
+
+        let res = cmds.input_cmd("directory ++more rust", ctx.clone());
+        let msg = "++more doesn't take any other arguments";
+        assert_eq!(res, Err(CommandError::Error(msg.into())));
+
+        let res = cmds.input_cmd("directory ++foo", ctx.clone());
+        assert_eq!(res, Err(CommandError::InvalidArgument));
     }
 }
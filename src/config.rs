@@ -565,6 +565,9 @@ pub struct TunableValues {
     pub image_preview: Option<ImagePreviewValues>,
     pub user_gutter_width: usize,
     pub external_edit_file_suffix: String,
+    pub away_after: Option<u64>,
+    pub refuse_unverified_devices: bool,
+    pub search_index: bool,
 }
 
 #[derive(Clone, Default, Deserialize)]
@@ -589,6 +592,9 @@ pub struct Tunables {
     pub image_preview: Option<ImagePreview>,
     pub user_gutter_width: Option<usize>,
     pub external_edit_file_suffix: Option<String>,
+    pub away_after: Option<u64>,
+    pub refuse_unverified_devices: Option<bool>,
+    pub search_index: Option<bool>,
 }
 
 impl Tunables {
@@ -619,6 +625,11 @@ impl Tunables {
             external_edit_file_suffix: self
                 .external_edit_file_suffix
                 .or(other.external_edit_file_suffix),
+            away_after: self.away_after.or(other.away_after),
+            refuse_unverified_devices: self
+                .refuse_unverified_devices
+                .or(other.refuse_unverified_devices),
+            search_index: self.search_index.or(other.search_index),
         }
     }
 
@@ -645,6 +656,9 @@ impl Tunables {
             external_edit_file_suffix: self
                 .external_edit_file_suffix
                 .unwrap_or_else(|| ".md".to_string()),
+            away_after: self.away_after,
+            refuse_unverified_devices: self.refuse_unverified_devices.unwrap_or(false),
+            search_index: self.search_index.unwrap_or(true),
         }
     }
 }
@@ -807,6 +821,7 @@ pub struct ApplicationSettings {
     pub session_json_old: PathBuf,
     pub sled_dir: PathBuf,
     pub sqlite_dir: PathBuf,
+    pub search_db: PathBuf,
     pub profile_name: String,
     pub profile: ProfileConfig,
     pub tunables: TunableValues,
@@ -899,6 +914,9 @@ impl ApplicationSettings {
         let mut sqlite_dir = profile_data_dir.clone();
         sqlite_dir.push("sqlite");
 
+        let mut search_db = profile_data_dir.clone();
+        search_db.push("search.sqlite3");
+
         let mut session_json = profile_data_dir.clone();
         session_json.push("session.json");
 
@@ -919,6 +937,7 @@ impl ApplicationSettings {
             session_json,
             session_json_old,
             sqlite_dir,
+            search_db,
             profile_name,
             profile,
             tunables,
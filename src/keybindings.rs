@@ -12,7 +12,7 @@ use modalkit::{
     prelude::*,
 };
 
-use crate::base::{IambAction, IambInfo, Keybindings, MATRIX_ID_WORD};
+use crate::base::{FoldAction, IambAction, IambInfo, Keybindings, MATRIX_ID_WORD};
 use crate::config::{ApplicationSettings, Keys};
 
 pub type IambStep = InputStep<IambInfo>;
@@ -59,6 +59,25 @@ pub fn setup_keybindings() -> Keybindings {
     ism.add_mapping(VimMode::Normal, &cwcm, &stoggle);
     ism.add_mapping(VimMode::Visual, &cwcm, &stoggle);
 
+    // Modalkit doesn't support per-window mappings, so these are mapped everywhere, but only
+    // space windows act on them, and they're ignored elsewhere.
+    let folds = vec![
+        ("zo", FoldAction::Open),
+        ("zc", FoldAction::Close),
+        ("za", FoldAction::Toggle),
+        ("zR", FoldAction::OpenAll),
+        ("zM", FoldAction::CloseAll),
+    ];
+
+    for (keys, act) in folds {
+        let keys = keys
+            .chars()
+            .map(|c| once(&c.to_string().parse::<TerminalKey>().unwrap()))
+            .collect::<Vec<_>>();
+        let step = IambStep::new().actions(vec![IambAction::Fold(act).into()]);
+        ism.add_mapping(VimMode::Normal, &keys, &step);
+    }
+
     let shift_enter = vec![once(&shift_enter)];
     let newline = IambStep::new().actions(vec![InsertTextAction::Type(
         Char::Single('\n').into(),
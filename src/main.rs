@@ -21,6 +21,7 @@ use std::fmt::Display;
 use std::fs::{create_dir_all, File};
 use std::io::{stdout, BufWriter, Stdout, Write};
 use std::ops::DerefMut;
+use std::path::Path;
 use std::process;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
@@ -29,7 +30,19 @@ use std::time::{Duration, Instant};
 use clap::Parser;
 use matrix_sdk::crypto::encrypt_room_key_export;
 use matrix_sdk::ruma::api::client::error::ErrorKind;
-use matrix_sdk::ruma::OwnedUserId;
+use matrix_sdk::ruma::api::client::uiaa::{self, AuthData, AuthType};
+use matrix_sdk::ruma::events::AnyMessageLikeEvent;
+use matrix_sdk::ruma::serde::Raw;
+use matrix_sdk::ruma::{
+    OwnedDeviceId,
+    OwnedEventId,
+    OwnedRoomId,
+    OwnedRoomOrAliasId,
+    OwnedUserId,
+    RoomOrAliasId,
+    UserId,
+};
+use matrix_sdk::{Client, RoomState as MatrixRoomState};
 use modalkit::keybindings::InputBindings;
 use rand::{distributions::Alphanumeric, Rng};
 use temp_dir::TempDir;
@@ -72,6 +85,7 @@ mod keybindings;
 mod message;
 mod notifications;
 mod preview;
+mod search;
 mod sled_export;
 mod util;
 mod windows;
@@ -84,20 +98,48 @@ use crate::{
     base::{
         AsyncProgramStore,
         ChatStore,
+        DevicesAction,
+        DirectoryAction,
+        DirectoryResults,
+        EventLocation,
         HomeserverAction,
         IambAction,
         IambError,
         IambId,
         IambInfo,
         IambResult,
+        IdentityAction,
         KeysAction,
+        ProfileAction,
         ProgramAction,
         ProgramContext,
         ProgramStore,
+        RawJsonAction,
+        SearchFlags,
+        SearchResults,
     },
     config::{ApplicationSettings, Iamb},
     windows::IambWindow,
-    worker::{create_room, ClientWorker, LoginStyle, Requester},
+    worker::{
+        create_room,
+        describe_identity,
+        edit_json,
+        get_message_event,
+        global_account_data,
+        key_backup_status,
+        list_devices,
+        public_rooms,
+        read_upload,
+        resolve_room,
+        restore_key_backup,
+        retry_decryption,
+        retry_decryption_all,
+        search_messages,
+        set_presence,
+        ClientWorker,
+        LoginStyle,
+        Requester,
+    },
 };
 
 use modalkit::{
@@ -348,6 +390,12 @@ impl Application {
 
     async fn step(&mut self) -> Result<TerminalKey, std::io::Error> {
         loop {
+            let notices = std::mem::take(&mut self.store.lock().await.application.notices);
+
+            for info in notices {
+                self.handle_info(info);
+            }
+
             self.redraw(self.dirty, self.store.clone().lock().await.deref_mut())?;
             self.dirty = false;
 
@@ -546,16 +594,39 @@ impl Application {
 
                 None
             },
+            IambAction::Fold(act) => {
+                self.screen.current_window_mut()?.fold(act)?;
+
+                None
+            },
 
+            IambAction::Devices(act) => {
+                let acts = self.devices_command(act, ctx, store).await?;
+                self.action_prepend(acts);
+
+                None
+            },
+            IambAction::Directory(act) => {
+                let acts = self.directory_command(act, ctx, store).await?;
+                self.action_prepend(acts);
+
+                None
+            },
             IambAction::Homeserver(act) => {
                 let acts = self.homeserver_command(act, ctx, store).await?;
                 self.action_prepend(acts);
 
                 None
             },
+            IambAction::Identity(act) => self.identity_command(act, ctx, store).await?,
             IambAction::Keys(act) => self.keys_command(act, ctx, store).await?,
+            IambAction::Profile(act) => self.profile_command(act, ctx, store).await?,
             IambAction::Message(act) => {
-                self.screen.current_window_mut()?.message_command(act, ctx, store).await?
+                let win = self.screen.current_window_mut()?;
+                let acts = win.message_command(act, ctx, store).await?;
+                self.action_prepend(acts);
+
+                None
             },
             IambAction::Room(act) => {
                 let acts = self.screen.current_window_mut()?.room_command(act, ctx, store).await?;
@@ -566,6 +637,15 @@ impl Application {
             IambAction::Send(act) => {
                 self.screen.current_window_mut()?.send_command(act, ctx, store).await?
             },
+            IambAction::Search(term, flags) => {
+                let acts = self.search_command(term, flags, ctx, store).await?;
+                self.action_prepend(acts);
+
+                None
+            },
+            IambAction::JumpToEvent(room_id, event_id) => {
+                self.jump_to_event(room_id, event_id, ctx, store).await?
+            },
 
             IambAction::OpenLink(url) => {
                 tokio::task::spawn_blocking(move || {
@@ -601,15 +681,152 @@ impl Application {
         store: &mut ProgramStore,
     ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
         match action {
-            HomeserverAction::CreateRoom(alias, vis, flags) => {
+            HomeserverAction::AccountData(RawJsonAction::Show(ty)) => {
+                let client = &store.application.worker.client;
+                let Some(msg) = global_account_data(client, &ty).await? else {
+                    let msg = format!("No {ty} account data has been set");
+                    let err = UIError::Failure(msg);
+
+                    return Err(err);
+                };
+
+                let act = Action::ShowInfoMessage(InfoMessage::Pager(msg));
+
+                Ok(vec![(act, ctx)])
+            },
+            HomeserverAction::AccountData(RawJsonAction::Edit(ty)) => {
+                let client = &store.application.worker.client;
+                let content = global_account_data(client, &ty).await?;
+                let content = content.unwrap_or_else(|| "{}".into());
+
+                let Some(value) = edit_json(&content)? else {
+                    return Ok(vec![]);
+                };
+
+                let raw = Raw::new(&value).map_err(IambError::from)?.cast();
+                let _ = client
+                    .account()
+                    .set_account_data_raw(ty.into(), raw)
+                    .await
+                    .map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
+            HomeserverAction::CreateRoom(alias, vis, flags, parent) => {
                 let client = &store.application.worker.client;
-                let room_id = create_room(client, alias, vis, flags).await?;
+                let parent = match parent {
+                    Some(parent) => Some(resolve_room(client, &parent).await?),
+                    None => None,
+                };
+                let room_id = create_room(client, alias, vis, flags, parent).await?;
                 let room = IambId::Room(room_id, None);
                 let target = OpenTarget::Application(room);
                 let action = WindowAction::Switch(target);
 
                 Ok(vec![(action.into(), ctx)])
             },
+            HomeserverAction::JoinRoom(room_id, servers) => {
+                let client = &store.application.worker.client;
+                let joined = client
+                    .get_room(&room_id)
+                    .is_some_and(|room| room.state() == MatrixRoomState::Joined);
+
+                if !joined {
+                    let room_or_alias = <&RoomOrAliasId>::from(&*room_id);
+                    let _ = client
+                        .join_room_by_id_or_alias(room_or_alias, &servers)
+                        .await
+                        .map_err(IambError::from)?;
+                }
+
+                let target = OpenTarget::Application(IambId::Room(room_id, None));
+                let action = WindowAction::Switch(target);
+
+                Ok(vec![(action.into(), ctx)])
+            },
+            HomeserverAction::Ignore(user) => {
+                let Ok(user_id) = OwnedUserId::try_from(user.as_str()) else {
+                    return Err(IambError::InvalidUserId(user).into());
+                };
+
+                let client = &store.application.worker.client;
+                client.account().ignore_user(&user_id).await.map_err(IambError::from)?;
+
+                let msg = format!("Ignoring {user_id}");
+                store.application.ignored.insert(user_id);
+
+                Ok(vec![(Action::ShowInfoMessage(msg.into()), ctx)])
+            },
+            HomeserverAction::Presence(presence, status_msg) => {
+                let client = &store.application.worker.client;
+                let user_id = &store.application.settings.profile.user_id;
+                set_presence(client, user_id, presence.clone(), status_msg.clone()).await?;
+
+                let msg = match &status_msg {
+                    Some(status) => format!("Set presence to {presence} ({status})"),
+                    None => format!("Set presence to {presence}"),
+                };
+
+                store.application.presences.insert(user_id.clone(), presence.clone());
+                store.application.presence = presence;
+                store.application.status_msg = status_msg;
+
+                Ok(vec![(Action::ShowInfoMessage(msg.into()), ctx)])
+            },
+            HomeserverAction::Unignore(user) => {
+                let Ok(user_id) = OwnedUserId::try_from(user.as_str()) else {
+                    return Err(IambError::InvalidUserId(user).into());
+                };
+
+                if !store.application.is_ignored(&user_id) {
+                    let msg = format!("{user_id} isn't being ignored");
+
+                    return Err(UIError::Failure(msg));
+                }
+
+                let client = &store.application.worker.client;
+                client.account().unignore_user(&user_id).await.map_err(IambError::from)?;
+
+                let msg = format!("No longer ignoring {user_id}");
+                store.application.ignored.remove(&user_id);
+
+                Ok(vec![(Action::ShowInfoMessage(msg.into()), ctx)])
+            },
+            HomeserverAction::ListIgnored => {
+                let mut ignored = store.application.ignored.iter().collect::<Vec<_>>();
+                ignored.sort();
+
+                let msg = if ignored.is_empty() {
+                    "You aren't ignoring anyone".to_string()
+                } else {
+                    let users = ignored.iter().map(|u| format!("  {u}")).collect::<Vec<_>>();
+                    format!("Ignored users:\n{}", users.join("\n"))
+                };
+
+                let act = Action::ShowInfoMessage(InfoMessage::Pager(msg));
+
+                Ok(vec![(act, ctx)])
+            },
+            HomeserverAction::Knock(room, reason) => {
+                let Ok(room) = OwnedRoomOrAliasId::try_from(room.as_str()) else {
+                    let msg = format!("{room:?} is not a valid room identifier or alias");
+                    let err = UIError::Failure(msg);
+
+                    return Err(err);
+                };
+
+                let servers = room.server_name().map(ToOwned::to_owned).into_iter().collect();
+                let client = &store.application.worker.client;
+                let _ = client
+                    .knock(room.clone(), reason, servers)
+                    .await
+                    .map_err(IambError::from)?;
+
+                let msg = format!("Asked to join {room}; you can open it once someone lets you in");
+                let act = Action::ShowInfoMessage(msg.into());
+
+                Ok(vec![(act, ctx)])
+            },
             HomeserverAction::Logout(user, true) => {
                 self.worker.logout(user)?;
                 let flags = CloseFlags::QUIT | CloseFlags::FORCE;
@@ -628,6 +845,310 @@ impl Application {
         }
     }
 
+    async fn devices_command(
+        &mut self,
+        action: DevicesAction,
+        ctx: ProgramContext,
+        store: &mut ProgramStore,
+    ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
+        let client = store.application.worker.client.clone();
+        let user_id = store.application.settings.profile.user_id.clone();
+
+        let acts = match action {
+            DevicesAction::List => {
+                let target = OpenTarget::Application(IambId::DeviceList);
+                let action = WindowAction::Switch(target);
+
+                vec![(action.into(), ctx)]
+            },
+            DevicesAction::Rename(device_id, name) => {
+                let _ = client.rename_device(&device_id, &name).await.map_err(IambError::from)?;
+
+                vec![]
+            },
+            DevicesAction::Delete(device_id, skip_confirm) => {
+                if client.device_id() == Some(device_id.as_ref()) {
+                    let msg = "Cannot delete the current device; use :logout instead";
+                    let err = UIError::Failure(msg.into());
+
+                    return Err(err);
+                }
+
+                if !skip_confirm {
+                    let msg = format!("Do you really want to delete and sign out {device_id}?");
+                    let act = IambAction::from(DevicesAction::Delete(device_id, true));
+                    let prompt = PromptYesNo::new(msg, vec![Action::from(act)]);
+                    let prompt = Box::new(prompt);
+
+                    return Err(UIError::NeedConfirm(prompt));
+                }
+
+                self.delete_device(&client, &user_id, device_id).await?;
+
+                vec![]
+            },
+            DevicesAction::Verify(device_id) => {
+                let info = self.worker.verify_device(device_id)?;
+                let target = OpenTarget::Application(IambId::VerifyList);
+                let mut acts = vec![(WindowAction::Switch(target).into(), ctx.clone())];
+
+                if let Some(info) = info {
+                    acts.push((Action::ShowInfoMessage(info), ctx));
+                }
+
+                acts
+            },
+        };
+
+        // Refresh the device list so that it reflects any changes.
+        store.application.devices = list_devices(&client, &user_id).await?;
+
+        Ok(acts)
+    }
+
+    /// Delete one of the user's devices, asking for their password if the homeserver requires
+    /// additional authentication.
+    async fn delete_device(
+        &mut self,
+        client: &Client,
+        user_id: &UserId,
+        device_id: OwnedDeviceId,
+    ) -> IambResult<()> {
+        let devices = [device_id];
+        let err = match client.delete_devices(&devices, None).await {
+            Ok(_) => return Ok(()),
+            Err(err) => err,
+        };
+
+        let Some(info) = err.as_uiaa_response() else {
+            return Err(IambError::from(err).into());
+        };
+
+        if !info.flows.iter().any(|flow| flow.stages.contains(&AuthType::Password)) {
+            let msg = "The homeserver requires an authentication method other than a password";
+            let err = UIError::Failure(msg.into());
+
+            return Err(err);
+        }
+
+        let password = self.prompt_password(&format!("Password for {user_id}: "))?;
+        let mut auth = uiaa::Password::new(user_id.to_owned().into(), password);
+        auth.session = info.session.clone();
+
+        let auth = Some(AuthData::Password(auth));
+        let _ = client.delete_devices(&devices, auth).await.map_err(IambError::from)?;
+
+        Ok(())
+    }
+
+    /// Temporarily leave the TUI to read a password from the terminal.
+    fn prompt_password(&mut self, prompt: &str) -> IambResult<String> {
+        crossterm::terminal::disable_raw_mode().map_err(IambError::from)?;
+        execute!(self.terminal.backend_mut(), LeaveAlternateScreen).map_err(IambError::from)?;
+
+        let password = rpassword::prompt_password(prompt);
+
+        execute!(self.terminal.backend_mut(), EnterAlternateScreen).map_err(IambError::from)?;
+        crossterm::terminal::enable_raw_mode().map_err(IambError::from)?;
+
+        password.map_err(|e| IambError::from(e).into())
+    }
+
+    async fn directory_command(
+        &mut self,
+        action: DirectoryAction,
+        ctx: ProgramContext,
+        store: &mut ProgramStore,
+    ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
+        match action {
+            DirectoryAction::Search(server, filter) => {
+                let client = &store.application.worker.client;
+                let (rooms, next_batch) =
+                    public_rooms(client, server.as_deref(), filter.clone(), None).await?;
+                store.application.directory =
+                    DirectoryResults { server, filter, rooms, next_batch };
+
+                let target = OpenTarget::Application(IambId::Directory);
+                let action = WindowAction::Switch(target);
+
+                Ok(vec![(action.into(), ctx)])
+            },
+            DirectoryAction::NextPage => {
+                let ChatStore { directory, worker, .. } = &mut store.application;
+
+                let Some(since) = directory.next_batch.clone() else {
+                    let msg = "There are no more rooms in the directory";
+                    let err = UIError::Failure(msg.into());
+
+                    return Err(err);
+                };
+
+                let server = directory.server.as_deref();
+                let filter = directory.filter.clone();
+                let (rooms, next_batch) =
+                    public_rooms(&worker.client, server, filter, Some(since)).await?;
+                directory.rooms.extend(rooms);
+                directory.next_batch = next_batch;
+
+                Ok(vec![])
+            },
+        }
+    }
+
+    async fn search_command(
+        &mut self,
+        term: String,
+        flags: SearchFlags,
+        ctx: ProgramContext,
+        store: &mut ProgramStore,
+    ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
+        let room_id = if flags.contains(SearchFlags::ALL) {
+            None
+        } else if let Some(IambId::Room(room_id, _)) = self.screen.current_window().map(Window::id)
+        {
+            Some(room_id)
+        } else {
+            return Err(IambError::NoSelectedRoom.into());
+        };
+
+        let results = if flags.contains(SearchFlags::LOCAL) {
+            store.application.index.search(&term, room_id.as_deref())?
+        } else {
+            let client = &store.application.worker.client;
+            search_messages(client, term.clone(), room_id).await?
+        };
+        store.application.search = SearchResults { term, results };
+
+        let target = OpenTarget::Application(IambId::SearchResults);
+        let action = WindowAction::Switch(target);
+
+        Ok(vec![(action.into(), ctx)])
+    }
+
+    async fn jump_to_event(
+        &mut self,
+        room_id: OwnedRoomId,
+        event_id: OwnedEventId,
+        ctx: ProgramContext,
+        store: &mut ProgramStore,
+    ) -> IambResult<EditInfo> {
+        if !store
+            .application
+            .get_room_info(room_id.clone())
+            .keys
+            .contains_key(&event_id)
+        {
+            // Fetch the message if it hasn't been loaded into the scrollback yet.
+            let client = &store.application.worker.client;
+            let ev = get_message_event(client, &room_id, &event_id).await?;
+
+            if let AnyMessageLikeEvent::RoomMessage(msg) = &ev {
+                store.application.index.insert(msg);
+            }
+
+            let info = store.application.get_room_info(room_id.clone());
+
+            match ev {
+                AnyMessageLikeEvent::RoomEncrypted(msg) => info.insert_encrypted(msg),
+                AnyMessageLikeEvent::RoomMessage(msg) => info.insert(msg),
+                _ => return Err(IambError::UnknownEvent(event_id).into()),
+            }
+        }
+
+        let info = store.application.get_room_info(room_id.clone());
+        let Some(EventLocation::Message(thread, key)) = info.keys.get(&event_id) else {
+            return Err(IambError::UnknownEvent(event_id).into());
+        };
+        let (thread, key) = (thread.clone(), key.clone());
+
+        let target = OpenTarget::Application(IambId::Room(room_id, thread));
+        let action = WindowAction::Switch(target);
+        let _ = self.screen.window_command(&action, &ctx, store)?;
+        self.screen.current_window_mut()?.goto_message(key)?;
+
+        Ok(None)
+    }
+
+    async fn profile_command(
+        &mut self,
+        action: ProfileAction,
+        _: ProgramContext,
+        store: &mut ProgramStore,
+    ) -> IambResult<EditInfo> {
+        let account = store.application.worker.client.account();
+
+        match action {
+            ProfileAction::SetName(name) => {
+                account.set_display_name(Some(&name)).await.map_err(IambError::from)?;
+
+                Ok(Some(format!("Set display name to {name:?}").into()))
+            },
+            ProfileAction::UnsetName => {
+                account.set_display_name(None).await.map_err(IambError::from)?;
+
+                Ok(Some("Removed display name".into()))
+            },
+            ProfileAction::SetAvatar(file) => {
+                let (_, mime, bytes) = read_upload(Path::new(file.as_str()))?;
+
+                if mime.type_() != mime::IMAGE {
+                    let msg = format!("{file} doesn't look like an image ({mime})");
+
+                    return Err(UIError::Failure(msg));
+                }
+
+                account.upload_avatar(&mime, bytes).await.map_err(IambError::from)?;
+
+                Ok(Some("Updated avatar".into()))
+            },
+        }
+    }
+
+    async fn identity_command(
+        &mut self,
+        action: IdentityAction,
+        _: ProgramContext,
+        store: &mut ProgramStore,
+    ) -> IambResult<EditInfo> {
+        let client = &store.application.worker.client;
+
+        match action {
+            IdentityAction::Show(user_id) => {
+                let violation = client.joined_rooms().iter().any(|room| {
+                    store
+                        .application
+                        .rooms
+                        .get(room.room_id())
+                        .is_some_and(|info| info.identity_violations.contains(&user_id))
+                });
+                let msg = describe_identity(client, &user_id, violation).await?;
+
+                Ok(Some(InfoMessage::Pager(msg)))
+            },
+            IdentityAction::Withdraw(user_id) => {
+                let Some(identity) = client
+                    .encryption()
+                    .get_user_identity(&user_id)
+                    .await
+                    .map_err(IambError::from)?
+                else {
+                    let msg = format!("{user_id} has not set up cross-signing");
+                    return Err(UIError::Failure(msg));
+                };
+
+                identity.withdraw_verification().await.map_err(IambError::from)?;
+
+                for room in client.joined_rooms() {
+                    if let Some(info) = store.application.rooms.get_mut(room.room_id()) {
+                        info.identity_violations.remove(&user_id);
+                    }
+                }
+
+                Ok(Some(format!("No longer treating {user_id} as verified").into()))
+            },
+        }
+    }
+
     async fn keys_command(
         &mut self,
         action: KeysAction,
@@ -652,12 +1173,63 @@ impl Application {
                     .map_err(IambError::from)?;
 
                 let msg = format!("Imported {} of {} keys", res.imported_count, res.total_count);
+                self.spawn_retry_decryption(None);
 
                 Ok(Some(msg.into()))
             },
+            KeysAction::BackupEnable(passphrase) => {
+                let recovery = encryption.recovery();
+                let enable = recovery.enable();
+                let enable = match passphrase.as_deref() {
+                    Some(passphrase) => enable.with_passphrase(passphrase),
+                    None => enable,
+                };
+                let key = enable.await.map_err(IambError::from)?;
+
+                let msg = format!(
+                    "Key backup and recovery have been enabled.\n\n\
+                     Your recovery key is:\n\n    {key}\n\n\
+                     Store it somewhere safe: it, or your passphrase if you set one, \
+                     is needed to decrypt your message history on new devices."
+                );
+
+                Ok(Some(InfoMessage::Pager(msg)))
+            },
+            KeysAction::BackupStatus => {
+                let client = &store.application.worker.client;
+                let msg = key_backup_status(client).await?;
+
+                Ok(Some(InfoMessage::Pager(msg)))
+            },
+            KeysAction::BackupRestore(key) => {
+                let client = &store.application.worker.client;
+                restore_key_backup(client, &self.store, &key).await?;
+
+                Ok(Some("Restored secrets, and started downloading room keys".into()))
+            },
+            KeysAction::Retry(room_id, session_id) => {
+                self.spawn_retry_decryption(Some((room_id, session_id)));
+
+                Ok(None)
+            },
         }
     }
 
+    /// Retry decrypting messages in the background, once the store is no longer locked.
+    fn spawn_retry_decryption(&self, target: Option<(OwnedRoomId, Option<String>)>) {
+        let client = self.worker.client.clone();
+        let store = self.store.clone();
+
+        tokio::spawn(async move {
+            match target {
+                Some((room_id, session_id)) => {
+                    retry_decryption(&client, &store, &room_id, session_id.as_deref()).await
+                },
+                None => retry_decryption_all(&client, &store).await,
+            }
+        });
+    }
+
     fn handle_info(&mut self, info: InfoMessage) {
         match info {
             InfoMessage::Message(info) => {
@@ -801,7 +1373,40 @@ async fn login(worker: &Requester, settings: &ApplicationSettings) -> IambResult
         }
     }
 
-    Ok(())
+    login_recovery(worker).await
+}
+
+// A fresh login has no room keys, so offer to fetch them from the key backup.
+async fn login_recovery(worker: &Requester) -> IambResult<()> {
+    let secret_storage = worker.client.encryption().secret_storage();
+
+    if !secret_storage.is_enabled().await.unwrap_or(false) {
+        return Ok(());
+    }
+
+    println!("Your account has key backup set up, which can be used to decrypt older messages.");
+
+    loop {
+        let key = rpassword::prompt_password(
+            "Recovery key or passphrase (leave empty to skip, or use :keys backup restore later): ",
+        )?;
+        let key = key.trim();
+
+        if key.is_empty() {
+            return Ok(());
+        }
+
+        match worker.client.encryption().recovery().recover(key).await {
+            Ok(()) => {
+                println!("* Successfully restored secrets from key backup");
+                return Ok(());
+            },
+            Err(e) => {
+                println!("Failed to restore from key backup: {e}");
+                continue;
+            },
+        }
+    }
 }
 
 fn print_exit<T: Display, N>(v: T) -> N {
@@ -14,27 +14,42 @@ use humansize::{format_size, DECIMAL};
 use serde_json::json;
 use unicode_width::UnicodeWidthStr;
 
+use matrix_sdk::deserialized_responses::{
+    AlgorithmInfo,
+    EncryptionInfo,
+    ShieldState,
+    ShieldStateCode,
+    UnableToDecryptInfo,
+    UnableToDecryptReason,
+    VerificationState,
+};
 use matrix_sdk::ruma::{
     events::{
         relation::Thread,
         room::{
             encrypted::{
+                EncryptedEventScheme,
                 OriginalRoomEncryptedEvent,
                 RedactedRoomEncryptedEvent,
                 RoomEncryptedEvent,
             },
             message::{
                 FormattedBody,
+                ImageMessageEventContent,
                 MessageFormat,
                 MessageType,
                 OriginalRoomMessageEvent,
                 RedactedRoomMessageEvent,
+                RedactedRoomMessageEventContent,
                 Relation,
                 RoomMessageEvent,
                 RoomMessageEventContent,
             },
             redaction::SyncRoomRedactionEvent,
         },
+        sticker::StickerEvent,
+        MessageLikeEvent,
+        MessageLikeUnsigned,
         RedactContent,
         RedactedUnsigned,
     },
@@ -44,10 +59,11 @@ use matrix_sdk::ruma::{
     OwnedUserId,
     RoomVersionId,
     UInt,
+    UserId,
 };
 
 use ratatui::{
-    style::{Modifier as StyleModifier, Style},
+    style::{Color, Modifier as StyleModifier, Style},
     symbols::line::THICK_VERTICAL,
     text::{Line, Span, Text},
 };
@@ -58,7 +74,7 @@ use ratatui_image::protocol::Protocol;
 
 use crate::config::ImagePreviewSize;
 use crate::{
-    base::RoomInfo,
+    base::{Poll, RoomInfo},
     config::ApplicationSettings,
     message::html::{parse_matrix_html, StyleTree},
     util::{replace_emojis_in_str, space, space_span, take_width, wrapped_text},
@@ -419,6 +435,44 @@ fn redaction_unsigned(ev: SyncRoomRedactionEvent) -> RedactedUnsigned {
     RedactedUnsigned::new(serde_json::from_value(redacted_because).unwrap())
 }
 
+/// Convert a sticker into an image message, so that it's shown and previewed like any other
+/// image, with its body as the fallback text.
+pub fn sticker_to_message(ev: StickerEvent) -> RoomMessageEvent {
+    let ev = match ev {
+        MessageLikeEvent::Original(ev) => ev,
+        MessageLikeEvent::Redacted(ev) => {
+            return RoomMessageEvent::Redacted(RedactedRoomMessageEvent {
+                content: RedactedRoomMessageEventContent::new(),
+                event_id: ev.event_id,
+                sender: ev.sender,
+                origin_server_ts: ev.origin_server_ts,
+                room_id: ev.room_id,
+                unsigned: ev.unsigned,
+            });
+        },
+    };
+
+    let content = ev.content;
+    let image = ImageMessageEventContent::new(content.body, content.source.into())
+        .info(Some(Box::new(content.info)));
+
+    let mut msg = RoomMessageEventContent::new(MessageType::Image(image));
+    msg.relates_to = match content.relates_to {
+        Some(Relation::Reply { in_reply_to }) => Some(Relation::Reply { in_reply_to }),
+        Some(Relation::Thread(thread)) => Some(Relation::Thread(thread)),
+        _ => None,
+    };
+
+    RoomMessageEvent::Original(OriginalRoomMessageEvent {
+        content: msg,
+        event_id: ev.event_id,
+        sender: ev.sender,
+        origin_server_ts: ev.origin_server_ts,
+        room_id: ev.room_id,
+        unsigned: MessageLikeUnsigned::new(),
+    })
+}
+
 #[derive(Clone)]
 pub enum MessageEvent {
     EncryptedOriginal(Box<OriginalRoomEncryptedEvent>),
@@ -449,6 +503,22 @@ impl MessageEvent {
         }
     }
 
+    /// The Megolm session that an undecrypted message was encrypted with.
+    pub fn megolm_session_id(&self) -> Option<&str> {
+        let MessageEvent::EncryptedOriginal(ev) = self else {
+            return None;
+        };
+
+        match &ev.content.scheme {
+            EncryptedEventScheme::MegolmV1AesSha2(c) => Some(c.session_id.as_str()),
+            _ => None,
+        }
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        matches!(self, MessageEvent::EncryptedOriginal(_) | MessageEvent::EncryptedRedacted(_))
+    }
+
     pub fn is_emote(&self) -> bool {
         matches!(
             self.content(),
@@ -624,6 +694,9 @@ struct MessageFormatter<'a> {
 
     /// Iterator over the users who have read up to this message.
     read: Option<hash_set::Iter<'a, OwnedUserId>>,
+
+    /// A marker for the message's encryption state, shown at the end of the user gutter.
+    trust: Option<Span<'a>>,
 }
 
 impl<'a> MessageFormatter<'a> {
@@ -651,7 +724,7 @@ impl<'a> MessageFormatter<'a> {
                 let user = self.user.take().unwrap_or(user_gutter_empty_span);
                 let time = self.time.take().unwrap_or(TIME_GUTTER_EMPTY_SPAN);
 
-                let mut line = vec![user];
+                let mut line = self.user_gutter(user);
                 line.extend(prev_line.spans);
                 line.push(time);
 
@@ -676,7 +749,7 @@ impl<'a> MessageFormatter<'a> {
                 let user = self.user.take().unwrap_or(user_gutter_empty_span);
                 let time = self.time.take().unwrap_or_else(|| Span::from(""));
 
-                let mut line = vec![user];
+                let mut line = self.user_gutter(user);
                 line.extend(prev_line.spans);
                 line.push(time);
 
@@ -684,7 +757,7 @@ impl<'a> MessageFormatter<'a> {
             },
             MessageColumns::Two => {
                 let user = self.user.take().unwrap_or(user_gutter_empty_span);
-                let mut line = vec![user];
+                let mut line = self.user_gutter(user);
                 line.extend(prev_line.spans);
 
                 text.lines.push(Line::from(line));
@@ -695,7 +768,7 @@ impl<'a> MessageFormatter<'a> {
                 }
 
                 let leading = space_span(2, style);
-                let mut line = vec![leading];
+                let mut line = self.user_gutter(leading);
                 line.extend(prev_line.spans);
 
                 text.lines.push(Line::from(line));
@@ -703,6 +776,22 @@ impl<'a> MessageFormatter<'a> {
         }
     }
 
+    /// Place the encryption marker, if there is one, in the trailing padding of the user gutter.
+    fn user_gutter(&mut self, user: Span<'a>) -> Vec<Span<'a>> {
+        let Some(trust) = self.trust.take() else {
+            return vec![user];
+        };
+
+        let Span { content, style } = user;
+        let Some(content) = content.strip_suffix("  ") else {
+            return vec![Span::styled(content, style)];
+        };
+
+        let user = Span::styled(content.to_string(), style);
+
+        return vec![user, trust, Span::raw(" ")];
+    }
+
     fn push_text(&mut self, append: Text<'a>, style: Style, text: &mut Text<'a>) {
         for line in append.lines.into_iter() {
             self.push_spans(line, style, text);
@@ -808,6 +897,47 @@ impl<'a> MessageFormatter<'a> {
 
         self.push_text(threaded.finish(), style, text);
     }
+
+    fn push_poll(&mut self, poll: &'a Poll, user_id: &UserId, style: Style, text: &mut Text<'a>) {
+        let Some(start) = &poll.start else {
+            return;
+        };
+
+        let tally = poll.tally();
+        let selected = poll.user_selections(user_id);
+        let disclosed = poll.ended.is_some() || !start.undisclosed;
+
+        for (idx, ((_, answer), count)) in start.answers.iter().zip(tally).enumerate() {
+            let mut printer =
+                printer::TextPrinter::new(self.width(), style, false, false).literal(true);
+            let marker = if selected.contains(&idx) {
+                "\u{25C9}"
+            } else {
+                "\u{25CB}"
+            };
+
+            printer.push_span_nobreak(Span::styled(format!(" {marker} {}. ", idx + 1), style));
+            printer.push_str(answer, style);
+
+            if disclosed {
+                let bold = style.add_modifier(StyleModifier::BOLD);
+                printer.push_span_nobreak(Span::styled(format!(" [{count}]"), bold));
+            }
+
+            self.push_text(printer.finish(), style, text);
+        }
+
+        let voters = poll.voters();
+        let votes = if voters == 1 { "vote" } else { "votes" };
+        let status = match (poll.ended.is_some(), disclosed) {
+            (true, _) => format!(" Poll ended: final results from {voters} {votes}"),
+            (false, true) => format!(" {voters} {votes} so far"),
+            (false, false) => format!(" {voters} {votes}; results are shown when the poll ends"),
+        };
+        let status = Span::styled(status, style.add_modifier(StyleModifier::ITALIC));
+
+        self.push_spans(Line::from(status), style, text);
+    }
 }
 
 pub enum ImageStatus {
@@ -817,6 +947,94 @@ pub enum ImageStatus {
     Error(String),
 }
 
+fn utd_reason(reason: &UnableToDecryptReason) -> &'static str {
+    match reason {
+        UnableToDecryptReason::MissingMegolmSession => "the room key hasn't been received",
+        UnableToDecryptReason::UnknownMegolmMessageIndex => {
+            "the room key that was received doesn't cover this message"
+        },
+        UnableToDecryptReason::MalformedEncryptedEvent => "the encrypted event is malformed",
+        UnableToDecryptReason::MegolmDecryptionFailure => "the room key failed to decrypt it",
+        UnableToDecryptReason::PayloadDeserializationFailure => "the decrypted event is invalid",
+        UnableToDecryptReason::MismatchedIdentityKeys => {
+            "the room key came from a device with mismatched identity keys"
+        },
+        UnableToDecryptReason::SenderIdentityNotTrusted(_) => "the sender's identity isn't trusted",
+        UnableToDecryptReason::Unknown => "unknown reason",
+    }
+}
+
+/// How a message in an encrypted room was protected.
+#[derive(Clone, Debug)]
+pub enum MessageEncryption {
+    /// The message was sent unencrypted.
+    Unencrypted,
+
+    /// The message was decrypted, and came from the described device.
+    Decrypted(Box<EncryptionInfo>),
+
+    /// The message could not be decrypted, for the given reason.
+    Undecryptable(UnableToDecryptInfo),
+}
+
+impl MessageEncryption {
+    /// Determine how a message was protected, given whether its room is encrypted.
+    ///
+    /// This returns [None] for messages in unencrypted rooms.
+    pub fn new(room_encrypted: bool, info: Option<&EncryptionInfo>) -> Option<Self> {
+        match info {
+            Some(info) => MessageEncryption::Decrypted(Box::new(info.clone())).into(),
+            None if room_encrypted => MessageEncryption::Unencrypted.into(),
+            None => None,
+        }
+    }
+
+    /// Pick whichever of two encryption states is less trustworthy, preferring the second when
+    /// they're equally trusted.
+    ///
+    /// This is used when an edit replaces a message's contents, so that the message is marked no
+    /// more trustworthy than the least trusted of its original and its edit.
+    pub fn weaker(original: Option<Self>, edit: Option<Self>) -> Option<Self> {
+        match (original, edit) {
+            (Some(original), Some(edit)) if original.trust() < edit.trust() => Some(original),
+            (original, None) => original,
+            (_, edit) => edit,
+        }
+    }
+
+    fn trust(&self) -> u8 {
+        match self {
+            MessageEncryption::Unencrypted => 0,
+            MessageEncryption::Undecryptable(_) => 0,
+            MessageEncryption::Decrypted(_) if self.is_verified() => 2,
+            MessageEncryption::Decrypted(_) => 1,
+        }
+    }
+
+    fn shield(&self) -> ShieldState {
+        match self {
+            MessageEncryption::Unencrypted => {
+                ShieldState::Red {
+                    code: ShieldStateCode::SentInClear,
+                    message: "This message was sent unencrypted in an encrypted room",
+                }
+            },
+            MessageEncryption::Decrypted(info) => info.verification_state.to_shield_state_lax(),
+            MessageEncryption::Undecryptable(_) => ShieldState::None,
+        }
+    }
+
+    fn is_verified(&self) -> bool {
+        match self {
+            MessageEncryption::Unencrypted => false,
+            MessageEncryption::Undecryptable(_) => false,
+            MessageEncryption::Decrypted(info) => {
+                matches!(info.verification_state, VerificationState::Verified)
+            },
+        }
+    }
+}
+
 pub struct Message {
     pub event: MessageEvent,
     pub sender: OwnedUserId,
@@ -824,6 +1042,7 @@ pub struct Message {
     pub downloaded: bool,
     pub html: Option<StyleTree>,
     pub image_preview: ImageStatus,
+    pub encryption: Option<MessageEncryption>,
 }
 
 impl Message {
@@ -838,9 +1057,82 @@ impl Message {
             downloaded,
             html,
             image_preview: ImageStatus::None,
+            encryption: None,
+        }
+    }
+
+    /// The gutter marker for this message's encryption state: a shield for messages from
+    /// verified devices, and a warning for ones that shouldn't be trusted.
+    fn show_trust(&self) -> Option<Span<'static>> {
+        let encryption = self.encryption.as_ref()?;
+
+        if encryption.is_verified() {
+            return Span::styled("\u{26E8}", Style::default().fg(Color::Green)).into();
+        }
+
+        match encryption.shield() {
+            ShieldState::Red { .. } => {
+                Span::styled("\u{26A0}", Style::default().fg(Color::Red)).into()
+            },
+            ShieldState::Grey { .. } => {
+                Span::styled("\u{26A0}", Style::default().fg(Color::DarkGray)).into()
+            },
+            ShieldState::None => None,
         }
     }
 
+    /// Describe how this message was encrypted, and whether its sender can be trusted.
+    pub fn encryption_details(&self) -> String {
+        let mut lines = vec![];
+
+        match &self.encryption {
+            None if self.event.is_encrypted() => {
+                lines.push("This message could not be decrypted.".to_string());
+            },
+            Some(MessageEncryption::Undecryptable(utd)) => {
+                let reason = utd_reason(&utd.reason);
+                lines.push(format!("This message could not be decrypted: {reason}."));
+
+                if let Some(session_id) = &utd.session_id {
+                    lines.push(format!("Session: {session_id}"));
+                }
+            },
+            None => {
+                lines.push("No encryption information is available for this message.".into());
+            },
+            Some(MessageEncryption::Unencrypted) => {
+                lines.push("This message was not encrypted, even though the room is.".into());
+            },
+            Some(MessageEncryption::Decrypted(info)) => {
+                let device = info.sender_device.as_ref().map_or("unknown", |d| d.as_str());
+                let verification = match &info.verification_state {
+                    VerificationState::Verified => "verified".to_string(),
+                    VerificationState::Unverified(level) => format!("unverified ({level})"),
+                };
+
+                lines.push(format!("Sender: {}", info.sender));
+                lines.push(format!("Device: {device}"));
+
+                match &info.algorithm_info {
+                    AlgorithmInfo::MegolmV1AesSha2 { curve25519_key, .. } => {
+                        lines.push("Algorithm: m.megolm.v1.aes-sha2".into());
+                        lines.push(format!("Sender key: {curve25519_key}"));
+                    },
+                }
+
+                lines.push(format!("Verification: {verification}"));
+            },
+        }
+
+        match self.encryption.as_ref().map(MessageEncryption::shield) {
+            Some(ShieldState::Red { message, .. }) => lines.push(format!("Warning: {message}")),
+            Some(ShieldState::Grey { message, .. }) => lines.push(format!("Notice: {message}")),
+            Some(ShieldState::None) | None => {},
+        }
+
+        return lines.join("\n");
+    }
+
     pub fn reply_to(&self) -> Option<OwnedEventId> {
         let content = match &self.event {
             MessageEvent::EncryptedOriginal(_) => return None,
@@ -913,6 +1205,7 @@ impl Message {
             _ => self.timestamp.show_date(),
         };
         let user_gutter = settings.tunables.user_gutter_width;
+        let trust = self.show_trust();
 
         if user_gutter + TIME_GUTTER + READ_GUTTER + MIN_MSG_LEN <= width &&
             settings.tunables.read_receipt_display
@@ -923,7 +1216,17 @@ impl Message {
             let time = self.timestamp.show_time();
             let read = info.event_receipts.get(self.event.event_id()).map(|read| read.iter());
 
-            MessageFormatter { settings, cols, orig, fill, user, date, time, read }
+            MessageFormatter {
+                settings,
+                cols,
+                orig,
+                fill,
+                user,
+                date,
+                time,
+                read,
+                trust,
+            }
         } else if user_gutter + TIME_GUTTER + MIN_MSG_LEN <= width {
             let cols = MessageColumns::Three;
             let fill = width - user_gutter - TIME_GUTTER;
@@ -931,7 +1234,17 @@ impl Message {
             let time = self.timestamp.show_time();
             let read = None;
 
-            MessageFormatter { settings, cols, orig, fill, user, date, time, read }
+            MessageFormatter {
+                settings,
+                cols,
+                orig,
+                fill,
+                user,
+                date,
+                time,
+                read,
+                trust,
+            }
         } else if user_gutter + MIN_MSG_LEN <= width {
             let cols = MessageColumns::Two;
             let fill = width - user_gutter;
@@ -939,7 +1252,17 @@ impl Message {
             let time = None;
             let read = None;
 
-            MessageFormatter { settings, cols, orig, fill, user, date, time, read }
+            MessageFormatter {
+                settings,
+                cols,
+                orig,
+                fill,
+                user,
+                date,
+                time,
+                read,
+                trust,
+            }
         } else {
             let cols = MessageColumns::One;
             let fill = width.saturating_sub(2);
@@ -947,7 +1270,17 @@ impl Message {
             let time = None;
             let read = None;
 
-            MessageFormatter { settings, cols, orig, fill, user, date, time, read }
+            MessageFormatter {
+                settings,
+                cols,
+                orig,
+                fill,
+                user,
+                date,
+                time,
+                read,
+                trust,
+            }
         }
     }
 
@@ -1003,6 +1336,10 @@ impl Message {
             fmt.push_spans(space_span(width, style).into(), style, &mut text);
         }
 
+        if let Some(poll) = info.polls.get(self.event.event_id()) {
+            fmt.push_poll(poll, &settings.profile.user_id, style, &mut text);
+        }
+
         if settings.tunables.reaction_display {
             let reactions = info.get_reactions(self.event.event_id());
             fmt.push_reactions(reactions, style, &mut text);
@@ -1036,7 +1373,16 @@ impl Message {
         if let Some(html) = &self.html {
             (html.to_text(width, style, hide_reply, emoji_shortcodes), None)
         } else {
-            let mut msg = self.event.body();
+            let mut msg = match (&self.event, &self.encryption) {
+                (
+                    MessageEvent::EncryptedOriginal(_),
+                    Some(MessageEncryption::Undecryptable(utd)),
+                ) => {
+                    let reason = utd_reason(&utd.reason);
+                    Cow::Owned(format!("[Unable to decrypt message: {reason}]"))
+                },
+                _ => self.event.body(),
+            };
             if emoji_shortcodes {
                 msg = Cow::Owned(replace_emojis_in_str(msg.as_ref()));
             }
@@ -1171,6 +1517,16 @@ pub mod tests {
             VideoMessageEventContent,
         },
         ImageInfo,
+        MediaSource,
+    };
+    use matrix_sdk::ruma::{
+        events::sticker::{
+            OriginalStickerEvent,
+            RedactedStickerEvent,
+            RedactedStickerEventContent,
+            StickerEventContent,
+        },
+        OwnedMxcUri,
     };
 
     use super::*;
@@ -1347,6 +1703,40 @@ pub mod tests {
         );
     }
 
+    #[test]
+    fn test_encryption_trust() {
+        let mut msg = mock_message2();
+        assert!(msg.show_trust().is_none());
+        assert_eq!(
+            msg.encryption_details(),
+            "No encryption information is available for this message."
+        );
+
+        msg.encryption = MessageEncryption::new(false, None);
+        assert!(msg.encryption.is_none());
+
+        msg.encryption = MessageEncryption::new(true, None);
+        let trust = msg.show_trust().unwrap();
+        assert_eq!(trust.content, "\u{26A0}");
+        assert_eq!(trust.style.fg, Some(Color::Red));
+        assert_eq!(
+            msg.encryption_details(),
+            "This message was not encrypted, even though the room is.\n\
+             Warning: This message was sent unencrypted in an encrypted room"
+        );
+
+        msg.encryption = Some(MessageEncryption::Undecryptable(UnableToDecryptInfo {
+            session_id: Some("session".into()),
+            reason: UnableToDecryptReason::MissingMegolmSession,
+        }));
+        assert!(msg.show_trust().is_none());
+        assert_eq!(
+            msg.encryption_details(),
+            "This message could not be decrypted: the room key hasn't been received.\n\
+             Session: session"
+        );
+    }
+
     #[test]
     fn test_display_attachment_size() {
         assert_eq!(
@@ -1425,4 +1815,61 @@ pub mod tests {
             "[Attached Video: Alt text (44 kB)]".to_string()
         );
     }
+
+    #[test]
+    fn test_sticker_to_message() {
+        let url: OwnedMxcUri = "mxc://matrix.org/jDErsDugkNlfavzLTjJNUKAH".into();
+        let content = StickerEventContent::new("Waving cat".into(), ImageInfo::default(), url);
+        let ev = OriginalStickerEvent {
+            content,
+            event_id: MSG1_EVID.clone(),
+            sender: TEST_USER1.clone(),
+            origin_server_ts: MilliSecondsSinceUnixEpoch::now(),
+            room_id: TEST_ROOM1_ID.clone(),
+            unsigned: Default::default(),
+        };
+
+        let RoomMessageEvent::Original(msg) = sticker_to_message(StickerEvent::Original(ev)) else {
+            panic!("sticker should convert to an unredacted message");
+        };
+
+        assert_eq!(msg.event_id, MSG1_EVID.clone());
+        assert_eq!(msg.sender, TEST_USER1.clone());
+        assert_eq!(body_cow_content(&msg.content), "[Attached Image: Waving cat]");
+
+        let MessageType::Image(image) = &msg.content.msgtype else {
+            panic!("sticker should convert to an image message");
+        };
+
+        let MediaSource::Plain(uri) = &image.source else {
+            panic!("sticker should keep its unencrypted media source");
+        };
+
+        assert_eq!(uri.as_str(), "mxc://matrix.org/jDErsDugkNlfavzLTjJNUKAH");
+
+        // Redacted stickers become redacted messages.
+        let ev = RedactedStickerEvent {
+            content: RedactedStickerEventContent::new(),
+            event_id: MSG2_EVID.clone(),
+            sender: TEST_USER1.clone(),
+            origin_server_ts: MilliSecondsSinceUnixEpoch::now(),
+            room_id: TEST_ROOM1_ID.clone(),
+            unsigned: RedactedUnsigned::new(
+                serde_json::from_value(json!({
+                    "content": {},
+                    "event_id": "$redaction:example.com",
+                    "sender": TEST_USER1.as_str(),
+                    "origin_server_ts": 1,
+                    "unsigned": {},
+                }))
+                .unwrap(),
+            ),
+        };
+
+        let RoomMessageEvent::Redacted(msg) = sticker_to_message(StickerEvent::Redacted(ev)) else {
+            panic!("redacted sticker should convert to a redacted message");
+        };
+
+        assert_eq!(msg.event_id, MSG2_EVID.clone());
+    }
 }
@@ -8,6 +8,7 @@ use matrix_sdk::{
         events::{room::message::MessageType, AnyMessageLikeEventContent, AnySyncTimelineEvent},
         serde::Raw,
         MilliSecondsSinceUnixEpoch,
+        OwnedUserId,
         RoomId,
     },
     Client,
@@ -56,6 +57,10 @@ pub async fn register_notifications(
 
                 match notification.event {
                     RawAnySyncOrStrippedTimelineEvent::Sync(e) => {
+                        if is_ignored_sender(&store, &e).await {
+                            return;
+                        }
+
                         match parse_full_notification(e, room, show_message).await {
                             Ok((summary, body, server_ts)) => {
                                 if server_ts < startup_ts {
@@ -175,6 +180,14 @@ fn is_focused(locked: &ProgramStore) -> bool {
     locked.application.focused
 }
 
+async fn is_ignored_sender(store: &AsyncProgramStore, event: &Raw<AnySyncTimelineEvent>) -> bool {
+    let Ok(Some(sender)) = event.get_field::<OwnedUserId>("sender") else {
+        return false;
+    };
+
+    store.lock().await.application.is_ignored(&sender)
+}
+
 async fn is_visible_room(store: &AsyncProgramStore, room_id: &RoomId) -> bool {
     let mut locked = store.lock().await;
 
@@ -0,0 +1,409 @@
+//! # Local message search
+//!
+//! The homeserver can't search the contents of end-to-end encrypted rooms, so we keep our own
+//! full-text index of every message we've decrypted and displayed, which can be queried without
+//! a network connection.
+use std::convert::TryFrom;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use tracing::warn;
+
+use matrix_sdk::ruma::{
+    events::room::message::{OriginalRoomMessageEvent, Relation, RoomMessageEvent},
+    EventId,
+    OwnedEventId,
+    OwnedRoomId,
+    OwnedUserId,
+    RoomId,
+    UInt,
+};
+
+use crate::base::{IambError, IambResult, SearchResult};
+use crate::message::MessageTimeStamp;
+
+const MAX_RESULTS: u32 = 50;
+
+/// Messages get indexed while the store is locked, so avoid waiting on a sync to disk after each
+/// write. Losing the most recent writes after a crash is fine, since they get reindexed when the
+/// messages are loaded again.
+const PRAGMAS: &str = "
+PRAGMA journal_mode = WAL;
+PRAGMA synchronous = NORMAL;
+";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    event_id TEXT PRIMARY KEY,
+    room_id TEXT NOT NULL,
+    sender TEXT NOT NULL,
+    timestamp INTEGER NOT NULL,
+    updated INTEGER NOT NULL,
+    body TEXT NOT NULL
+);
+
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts
+    USING fts5(body, content='messages', content_rowid='rowid');
+
+CREATE TRIGGER IF NOT EXISTS messages_ai AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts(rowid, body) VALUES (new.rowid, new.body);
+END;
+
+CREATE TRIGGER IF NOT EXISTS messages_ad AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, body) VALUES ('delete', old.rowid, old.body);
+END;
+
+CREATE TRIGGER IF NOT EXISTS messages_au AFTER UPDATE ON messages BEGIN
+    INSERT INTO messages_fts(messages_fts, rowid, body) VALUES ('delete', old.rowid, old.body);
+    INSERT INTO messages_fts(rowid, body) VALUES (new.rowid, new.body);
+END;
+";
+
+/// Quote each of the user's search terms so that FTS5 doesn't interpret them as query syntax.
+fn fts_query(term: &str) -> String {
+    term.split_whitespace()
+        .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A full-text index of the messages that we've seen.
+pub struct MessageIndex {
+    conn: Connection,
+}
+
+impl MessageIndex {
+    /// Open the index stored at the given path.
+    ///
+    /// If the on-disk index can't be used, then we fall back to an in-memory index so that
+    /// searching still works for messages seen during this session.
+    pub fn open(path: &Path) -> Self {
+        match Connection::open(path).and_then(MessageIndex::setup) {
+            Ok(index) => index,
+            Err(e) => {
+                warn!(err = %e, path = %path.display(), "Failed to open message search index");
+
+                MessageIndex::in_memory()
+            },
+        }
+    }
+
+    /// Create a new index that only lives in memory.
+    pub fn in_memory() -> Self {
+        Connection::open_in_memory()
+            .and_then(MessageIndex::setup)
+            .expect("failed to create in-memory message index")
+    }
+
+    fn setup(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(PRAGMAS)?;
+        conn.execute_batch(SCHEMA)?;
+
+        Ok(MessageIndex { conn })
+    }
+
+    /// Index the text of a message.
+    ///
+    /// Older messages get loaded after newer ones when fetching scrollback, so the text is only
+    /// replaced when `updated`, the time of the event it came from, is newer than what's indexed.
+    fn upsert(
+        &self,
+        event_id: &EventId,
+        room_id: &RoomId,
+        sender: &str,
+        timestamp: UInt,
+        updated: UInt,
+        body: &str,
+    ) -> rusqlite::Result<()> {
+        let _ = self.conn.execute(
+            "INSERT INTO messages (event_id, room_id, sender, timestamp, updated, body)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(event_id) DO UPDATE SET body = excluded.body, updated = excluded.updated
+             WHERE excluded.updated > messages.updated",
+            params![
+                event_id.as_str(),
+                room_id.as_str(),
+                sender,
+                u64::from(timestamp),
+                u64::from(updated),
+                body
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Record who sent a message and when, in case one of its edits was indexed first.
+    fn set_origin(
+        &self,
+        event_id: &EventId,
+        sender: &str,
+        timestamp: UInt,
+    ) -> rusqlite::Result<()> {
+        let _ = self.conn.execute(
+            "UPDATE messages SET sender = ?2, timestamp = ?3
+             WHERE event_id = ?1 AND (sender != ?2 OR timestamp != ?3)",
+            params![event_id.as_str(), sender, u64::from(timestamp)],
+        )?;
+
+        Ok(())
+    }
+
+    fn insert_original(&self, ev: &OriginalRoomMessageEvent) -> rusqlite::Result<()> {
+        let sender = ev.sender.as_str();
+        let ts = ev.origin_server_ts.0;
+
+        if let Some(Relation::Replacement(repl)) = &ev.content.relates_to {
+            // Index the edited text under the original message.
+            let body = repl.new_content.msgtype.body();
+            self.upsert(&repl.event_id, &ev.room_id, sender, ts, ts, body)
+        } else {
+            let body = ev.content.body();
+            self.upsert(&ev.event_id, &ev.room_id, sender, ts, ts, body)?;
+            self.set_origin(&ev.event_id, sender, ts)
+        }
+    }
+
+    /// Add a message to the index.
+    pub fn insert(&self, msg: &RoomMessageEvent) {
+        let RoomMessageEvent::Original(ev) = msg else {
+            // Redacted messages have no text to index.
+            return self.remove(msg.event_id());
+        };
+
+        if let Err(e) = self.batch(|| self.insert_original(ev)) {
+            warn!(err = %e, event_id = ev.event_id.as_str(), "Failed to index message");
+        }
+    }
+
+    /// Run `f` inside of a single transaction, so that all of the writes it makes to the index
+    /// get committed together, such as when inserting a page of scrollback.
+    ///
+    /// If a batch is already in progress, then `f`'s writes become part of it.
+    pub fn batch<T>(&self, f: impl FnOnce() -> T) -> T {
+        if !self.conn.is_autocommit() {
+            return f();
+        }
+
+        let tx = match self.conn.unchecked_transaction() {
+            Ok(tx) => tx,
+            Err(e) => {
+                warn!(err = %e, "Failed to start a message index transaction");
+                return f();
+            },
+        };
+
+        let res = f();
+
+        if let Err(e) = tx.commit() {
+            warn!(err = %e, "Failed to commit message index transaction");
+        }
+
+        return res;
+    }
+
+    /// Remove a message from the index, such as after it's been redacted.
+    pub fn remove(&self, event_id: &EventId) {
+        let res = self
+            .conn
+            .execute("DELETE FROM messages WHERE event_id = ?1", params![event_id.as_str()]);
+
+        if let Err(e) = res {
+            warn!(err = %e, event_id = event_id.as_str(), "Failed to remove indexed message");
+        }
+    }
+
+    /// Check whether a message has been indexed.
+    pub fn contains(&self, event_id: &EventId) -> bool {
+        self.conn
+            .query_row(
+                "SELECT 1 FROM messages WHERE event_id = ?1",
+                params![event_id.as_str()],
+                |_| Ok(()),
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .is_some()
+    }
+
+    /// Search the indexed messages, most recent first.
+    ///
+    /// When `room_id` is [None], messages from every room are searched.
+    pub fn search(&self, term: &str, room_id: Option<&RoomId>) -> IambResult<Vec<SearchResult>> {
+        self.query(term, room_id).map_err(|e| IambError::SearchIndex(e).into())
+    }
+
+    fn query(&self, term: &str, room_id: Option<&RoomId>) -> rusqlite::Result<Vec<SearchResult>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT m.room_id, m.event_id, m.sender, m.timestamp, m.body
+             FROM messages_fts f JOIN messages m ON m.rowid = f.rowid
+             WHERE messages_fts MATCH ?1 AND (?2 IS NULL OR m.room_id = ?2)
+             ORDER BY m.timestamp DESC
+             LIMIT ?3",
+        )?;
+
+        let room_id = room_id.map(RoomId::as_str);
+        let rows = stmt.query_map(params![fts_query(term), room_id, MAX_RESULTS], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, u64>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+
+        let mut results = vec![];
+
+        for row in rows {
+            let (room_id, event_id, sender, timestamp, body) = row?;
+
+            let (Ok(room_id), Ok(event_id), Ok(sender), Ok(timestamp)) = (
+                OwnedRoomId::try_from(room_id),
+                OwnedEventId::try_from(event_id),
+                OwnedUserId::try_from(sender),
+                UInt::try_from(timestamp),
+            ) else {
+                continue;
+            };
+
+            let timestamp = MessageTimeStamp::from(timestamp);
+
+            results.push(SearchResult { room_id, event_id, sender, timestamp, body });
+        }
+
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use matrix_sdk::ruma::{
+        event_id,
+        events::room::message::{ReplacementMetadata, RoomMessageEventContent},
+        events::MessageLikeUnsigned,
+        room_id,
+        MilliSecondsSinceUnixEpoch,
+    };
+
+    fn mock_message(
+        event_id: &EventId,
+        room_id: &RoomId,
+        ts: u32,
+        content: RoomMessageEventContent,
+    ) -> RoomMessageEvent {
+        RoomMessageEvent::Original(OriginalRoomMessageEvent {
+            content,
+            event_id: event_id.to_owned(),
+            sender: TEST_USER1.clone(),
+            origin_server_ts: MilliSecondsSinceUnixEpoch(ts.into()),
+            room_id: room_id.to_owned(),
+            unsigned: MessageLikeUnsigned::new(),
+        })
+    }
+
+    #[test]
+    fn test_index_search() {
+        let index = MessageIndex::in_memory();
+        let room1 = room_id!("!room1:example.com");
+        let room2 = room_id!("!room2:example.com");
+
+        let content = RoomMessageEventContent::text_plain("hello world");
+        index.insert(&mock_message(event_id!("$a:example.com"), room1, 1, content));
+        let content = RoomMessageEventContent::text_plain("hello there");
+        index.insert(&mock_message(event_id!("$b:example.com"), room2, 2, content));
+        let content = RoomMessageEventContent::text_plain("goodbye \"world\"");
+        index.insert(&mock_message(event_id!("$c:example.com"), room1, 3, content));
+
+        // Results are ordered most recent first.
+        let res = index.search("hello", None).unwrap();
+        let ids = res.iter().map(|r| r.event_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["$b:example.com", "$a:example.com"]);
+
+        // Searching can be limited to a single room.
+        let res = index.search("hello", Some(room1)).unwrap();
+        let ids = res.iter().map(|r| r.event_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["$a:example.com"]);
+
+        // Every term needs to match, and punctuation isn't treated as query syntax.
+        let res = index.search("\"world\" hello", None).unwrap();
+        let ids = res.iter().map(|r| r.event_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["$a:example.com"]);
+
+        // Removed messages no longer show up.
+        index.remove(event_id!("$a:example.com"));
+        assert!(!index.contains(event_id!("$a:example.com")));
+        let res = index.search("world", None).unwrap();
+        let ids = res.iter().map(|r| r.event_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["$c:example.com"]);
+    }
+
+    #[test]
+    fn test_index_edit() {
+        let index = MessageIndex::in_memory();
+        let room1 = room_id!("!room1:example.com");
+        let orig = event_id!("$a:example.com");
+
+        let content = RoomMessageEventContent::text_plain("hello wrold");
+        index.insert(&mock_message(orig, room1, 1, content));
+
+        let metadata = ReplacementMetadata::new(orig.to_owned(), None);
+        let content =
+            RoomMessageEventContent::text_plain("hello world").make_replacement(metadata, None);
+        index.insert(&mock_message(event_id!("$b:example.com"), room1, 2, content));
+
+        // The edit replaces the original text instead of being indexed separately.
+        let res = index.search("world", None).unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].event_id.as_str(), orig.as_str());
+        assert_eq!(res[0].body, "hello world");
+        assert!(index.search("wrold", None).unwrap().is_empty());
+        assert!(!index.contains(event_id!("$b:example.com")));
+    }
+
+    #[test]
+    fn test_index_edit_before_original() {
+        let index = MessageIndex::in_memory();
+        let room1 = room_id!("!room1:example.com");
+        let orig = event_id!("$a:example.com");
+
+        // Fetching scrollback loads the edit before the message it replaces.
+        let metadata = ReplacementMetadata::new(orig.to_owned(), None);
+        let content =
+            RoomMessageEventContent::text_plain("hello world").make_replacement(metadata, None);
+        index.insert(&mock_message(event_id!("$b:example.com"), room1, 2, content));
+
+        let content = RoomMessageEventContent::text_plain("hello wrold");
+        index.insert(&mock_message(orig, room1, 1, content));
+
+        // The edited text is kept, but the time comes from the original message.
+        let res = index.search("world", None).unwrap();
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].event_id.as_str(), orig.as_str());
+        assert_eq!(res[0].body, "hello world");
+        assert_eq!(res[0].timestamp, MessageTimeStamp::from(UInt::from(1u32)));
+        assert!(index.search("wrold", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_index_batch() {
+        let index = MessageIndex::in_memory();
+        let room1 = room_id!("!room1:example.com");
+
+        index.batch(|| {
+            let content = RoomMessageEventContent::text_plain("hello world");
+            index.insert(&mock_message(event_id!("$a:example.com"), room1, 1, content));
+            let content = RoomMessageEventContent::text_plain("hello there");
+            index.insert(&mock_message(event_id!("$b:example.com"), room1, 2, content));
+        });
+
+        // Everything inserted during the batch gets committed together.
+        assert!(index.conn.is_autocommit());
+        let res = index.search("hello", None).unwrap();
+        let ids = res.iter().map(|r| r.event_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(ids, vec!["$b:example.com", "$a:example.com"]);
+    }
+}
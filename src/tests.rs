@@ -196,6 +196,9 @@ pub fn mock_tunables() -> TunableValues {
         },
         image_preview: None,
         user_gutter_width: 30,
+        away_after: None,
+        refuse_unverified_devices: false,
+        search_index: true,
     }
 }
 
@@ -206,6 +209,7 @@ pub fn mock_settings() -> ApplicationSettings {
         session_json_old: PathBuf::new(),
         sled_dir: PathBuf::new(),
         sqlite_dir: PathBuf::new(),
+        search_db: PathBuf::new(),
 
         profile_name: "test".into(),
         profile: ProfileConfig {
@@ -12,23 +12,30 @@ use std::ops::Deref;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Local as LocalTz};
 use matrix_sdk::{
     encryption::verification::{format_emojis, SasVerification},
     room::{Room as MatrixRoom, RoomMember},
     ruma::{
+        directory::PublicRoomsChunk,
         events::room::member::MembershipState,
         events::tag::{TagName, Tags},
+        presence::PresenceState,
+        OwnedEventId,
         OwnedRoomAliasId,
         OwnedRoomId,
+        OwnedUserId,
         RoomAliasId,
         RoomId,
+        ServerName,
+        UInt,
     },
 };
 
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Rect},
-    style::{Modifier as StyleModifier, Style},
+    style::{Color, Modifier as StyleModifier, Style},
     text::{Line, Span, Text},
     widgets::StatefulWidget,
 };
@@ -59,6 +66,10 @@ use modalkit_ratatui::{
 
 use crate::base::{
     ChatStore,
+    DeviceEntry,
+    FoldAction,
+    HomeserverAction,
+    IambAction,
     IambBufferId,
     IambError,
     IambId,
@@ -70,6 +81,8 @@ use crate::base::{
     ProgramContext,
     ProgramStore,
     RoomAction,
+    RoomInfo,
+    SearchResult,
     SendAction,
     SortColumn,
     SortFieldRoom,
@@ -78,10 +91,11 @@ use crate::base::{
     UnreadInfo,
 };
 
-use self::{room::RoomState, welcome::WelcomeState};
-use crate::message::MessageTimeStamp;
+use self::{room::RoomState, source::SourceState, welcome::WelcomeState};
+use crate::message::{MessageKey, MessageTimeStamp};
 
 pub mod room;
+pub mod source;
 pub mod welcome;
 
 type MatrixRoomInfo = Arc<(MatrixRoom, Option<Tags>)>;
@@ -112,6 +126,18 @@ fn selected_style(selected: bool) -> Style {
     }
 }
 
+/// A dot showing whether a user is around, if we know their presence.
+fn presence_span(presence: Option<&PresenceState>) -> Option<Span<'static>> {
+    let (dot, color) = match presence? {
+        PresenceState::Online => ("● ", Color::Green),
+        PresenceState::Unavailable => ("● ", Color::Yellow),
+        PresenceState::Offline => ("○ ", Color::DarkGray),
+        _ => return None,
+    };
+
+    Span::styled(dot, Style::default().fg(color)).into()
+}
+
 #[inline]
 fn selected_span(s: &str, selected: bool) -> Span {
     Span::styled(s, selected_style(selected))
@@ -310,12 +336,17 @@ macro_rules! delegate {
             IambWindow::Room($id) => $e,
             IambWindow::DirectList($id) => $e,
             IambWindow::MemberList($id, _, _) => $e,
+            IambWindow::PinnedList($id, _) => $e,
             IambWindow::RoomList($id) => $e,
             IambWindow::SpaceList($id) => $e,
             IambWindow::VerifyList($id) => $e,
             IambWindow::Welcome($id) => $e,
+            IambWindow::Source($id) => $e,
             IambWindow::ChatList($id) => $e,
             IambWindow::UnreadList($id) => $e,
+            IambWindow::SearchResults($id) => $e,
+            IambWindow::Directory($id) => $e,
+            IambWindow::DeviceList($id) => $e,
         }
     };
 }
@@ -323,13 +354,18 @@ macro_rules! delegate {
 pub enum IambWindow {
     DirectList(DirectListState),
     MemberList(MemberListState, OwnedRoomId, Option<Instant>),
+    PinnedList(PinnedListState, OwnedRoomId),
     Room(RoomState),
     VerifyList(VerifyListState),
     RoomList(RoomListState),
     SpaceList(SpaceListState),
     Welcome(WelcomeState),
+    Source(SourceState),
     ChatList(ChatListState),
     UnreadList(UnreadListState),
+    SearchResults(SearchResultsState),
+    Directory(DirectoryState),
+    DeviceList(DeviceListState),
 }
 
 impl IambWindow {
@@ -341,12 +377,29 @@ impl IambWindow {
         }
     }
 
+    pub fn fold(&mut self, act: FoldAction) -> IambResult<()> {
+        if let IambWindow::Room(w) = self {
+            w.fold(act)
+        } else {
+            // Folding only applies to space windows.
+            return Ok(());
+        }
+    }
+
+    pub fn goto_message(&mut self, key: MessageKey) -> IambResult<()> {
+        if let IambWindow::Room(w) = self {
+            w.goto_message(key)
+        } else {
+            return Err(IambError::NoSelectedRoom.into());
+        }
+    }
+
     pub async fn message_command(
         &mut self,
         act: MessageAction,
         ctx: ProgramContext,
         store: &mut ProgramStore,
-    ) -> IambResult<EditInfo> {
+    ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
         if let IambWindow::Room(w) = self {
             w.message_command(act, ctx, store).await
         } else {
@@ -383,11 +436,15 @@ impl IambWindow {
 
 pub type DirectListState = ListState<DirectItem, IambInfo>;
 pub type MemberListState = ListState<MemberItem, IambInfo>;
+pub type PinnedListState = ListState<PinnedItem, IambInfo>;
 pub type RoomListState = ListState<RoomItem, IambInfo>;
 pub type ChatListState = ListState<GenericChatItem, IambInfo>;
 pub type UnreadListState = ListState<GenericChatItem, IambInfo>;
 pub type SpaceListState = ListState<SpaceItem, IambInfo>;
 pub type VerifyListState = ListState<VerifyItem, IambInfo>;
+pub type SearchResultsState = ListState<SearchItem, IambInfo>;
+pub type DirectoryState = ListState<DirectoryItem, IambInfo>;
+pub type DeviceListState = ListState<DeviceItem, IambInfo>;
 
 impl From<ChatListState> for IambWindow {
     fn from(list: ChatListState) -> Self {
@@ -425,12 +482,36 @@ impl From<SpaceListState> for IambWindow {
     }
 }
 
+impl From<SearchResultsState> for IambWindow {
+    fn from(list: SearchResultsState) -> Self {
+        IambWindow::SearchResults(list)
+    }
+}
+
+impl From<DirectoryState> for IambWindow {
+    fn from(list: DirectoryState) -> Self {
+        IambWindow::Directory(list)
+    }
+}
+
+impl From<DeviceListState> for IambWindow {
+    fn from(list: DeviceListState) -> Self {
+        IambWindow::DeviceList(list)
+    }
+}
+
 impl From<WelcomeState> for IambWindow {
     fn from(win: WelcomeState) -> Self {
         IambWindow::Welcome(win)
     }
 }
 
+impl From<SourceState> for IambWindow {
+    fn from(win: SourceState) -> Self {
+        IambWindow::Source(win)
+    }
+}
+
 impl Editable<ProgramContext, ProgramStore, IambInfo> for IambWindow {
     fn editor_command(
         &mut self,
@@ -531,6 +612,31 @@ impl WindowOps<IambInfo> for IambWindow {
                     .focus(focused)
                     .render(area, buf, state);
             },
+            IambWindow::PinnedList(state, room_id) => {
+                let pinned = store
+                    .application
+                    .worker
+                    .client
+                    .get_room(room_id)
+                    .and_then(|room| room.pinned_event_ids())
+                    .unwrap_or_default();
+                let info = store.application.rooms.get_or_default(room_id.clone());
+
+                // Show the most recently pinned messages first.
+                let items = pinned
+                    .into_iter()
+                    .rev()
+                    .map(|event_id| PinnedItem::new(event_id, room_id.clone(), info))
+                    .collect::<Vec<_>>();
+
+                state.set(items);
+
+                List::new(store)
+                    .empty_message("No pinned messages in this room")
+                    .empty_alignment(Alignment::Center)
+                    .focus(focused)
+                    .render(area, buf, state);
+            },
             IambWindow::RoomList(state) => {
                 let mut items = store
                     .application
@@ -650,7 +756,61 @@ impl WindowOps<IambInfo> for IambWindow {
                     .focus(focused)
                     .render(area, buf, state);
             },
+            IambWindow::SearchResults(state) => {
+                let items = store
+                    .application
+                    .search
+                    .results
+                    .clone()
+                    .into_iter()
+                    .map(|result| SearchItem::new(result, store))
+                    .collect::<Vec<_>>();
+
+                state.set(items);
+
+                List::new(store)
+                    .empty_message("No messages matched your search")
+                    .empty_alignment(Alignment::Center)
+                    .focus(focused)
+                    .render(area, buf, state);
+            },
+            IambWindow::Directory(state) => {
+                let items = store
+                    .application
+                    .directory
+                    .rooms
+                    .iter()
+                    .cloned()
+                    .map(DirectoryItem::from)
+                    .collect::<Vec<_>>();
+
+                state.set(items);
+
+                List::new(store)
+                    .empty_message("No public rooms found")
+                    .empty_alignment(Alignment::Center)
+                    .focus(focused)
+                    .render(area, buf, state);
+            },
+            IambWindow::DeviceList(state) => {
+                let items = store
+                    .application
+                    .devices
+                    .iter()
+                    .cloned()
+                    .map(DeviceItem::from)
+                    .collect::<Vec<_>>();
+
+                state.set(items);
+
+                List::new(store)
+                    .empty_message("No devices found; run :devices to refresh the list")
+                    .empty_alignment(Alignment::Center)
+                    .focus(focused)
+                    .render(area, buf, state);
+            },
             IambWindow::Welcome(state) => state.draw(area, buf, focused, store),
+            IambWindow::Source(state) => state.draw(area, buf, focused, store),
         }
     }
 
@@ -661,12 +821,19 @@ impl WindowOps<IambInfo> for IambWindow {
             IambWindow::MemberList(w, room_id, last_fetch) => {
                 IambWindow::MemberList(w.dup(store), room_id.clone(), *last_fetch)
             },
+            IambWindow::PinnedList(w, room_id) => {
+                IambWindow::PinnedList(w.dup(store), room_id.clone())
+            },
             IambWindow::RoomList(w) => w.dup(store).into(),
             IambWindow::SpaceList(w) => w.dup(store).into(),
             IambWindow::VerifyList(w) => w.dup(store).into(),
             IambWindow::Welcome(w) => w.dup(store).into(),
+            IambWindow::Source(w) => w.dup(store).into(),
             IambWindow::ChatList(w) => w.dup(store).into(),
             IambWindow::UnreadList(w) => w.dup(store).into(),
+            IambWindow::SearchResults(w) => w.dup(store).into(),
+            IambWindow::Directory(w) => w.dup(store).into(),
+            IambWindow::DeviceList(w) => w.dup(store).into(),
         }
     }
 
@@ -702,12 +869,17 @@ impl Window<IambInfo> for IambWindow {
             IambWindow::Room(room) => IambId::Room(room.id().to_owned(), room.thread().cloned()),
             IambWindow::DirectList(_) => IambId::DirectList,
             IambWindow::MemberList(_, room_id, _) => IambId::MemberList(room_id.clone()),
+            IambWindow::PinnedList(_, room_id) => IambId::PinnedList(room_id.clone()),
             IambWindow::RoomList(_) => IambId::RoomList,
             IambWindow::SpaceList(_) => IambId::SpaceList,
             IambWindow::VerifyList(_) => IambId::VerifyList,
             IambWindow::Welcome(_) => IambId::Welcome,
+            IambWindow::Source(w) => IambId::Source(w.room_id().clone(), w.event_id().clone()),
             IambWindow::ChatList(_) => IambId::ChatList,
             IambWindow::UnreadList(_) => IambId::UnreadList,
+            IambWindow::SearchResults(_) => IambId::SearchResults,
+            IambWindow::Directory(_) => IambId::Directory,
+            IambWindow::DeviceList(_) => IambId::DeviceList,
         }
     }
 
@@ -718,8 +890,16 @@ impl Window<IambInfo> for IambWindow {
             IambWindow::SpaceList(_) => bold_spans("Spaces"),
             IambWindow::VerifyList(_) => bold_spans("Verifications"),
             IambWindow::Welcome(_) => bold_spans("Welcome to iamb"),
+            IambWindow::Source(w) => {
+                let title = store.application.get_room_title(w.room_id());
+                let v = vec![bold_span("Event Source: "), title.into()];
+                Line::from(v)
+            },
             IambWindow::ChatList(_) => bold_spans("DMs & Rooms"),
             IambWindow::UnreadList(_) => bold_spans("Unread Messages"),
+            IambWindow::SearchResults(_) => bold_spans("Search Results"),
+            IambWindow::Directory(_) => bold_spans("Room Directory"),
+            IambWindow::DeviceList(_) => bold_spans("Devices"),
 
             IambWindow::Room(w) => {
                 let title = store.application.get_room_title(w.id());
@@ -736,6 +916,16 @@ impl Window<IambInfo> for IambWindow {
                 ];
                 Line::from(v)
             },
+            IambWindow::PinnedList(state, room_id) => {
+                let title = store.application.get_room_title(room_id.as_ref());
+                let n = state.len();
+                let v = vec![
+                    bold_span("Pinned Messages "),
+                    Span::styled(format!("({n}): "), bold_style()),
+                    title.into(),
+                ];
+                Line::from(v)
+            },
         }
     }
 
@@ -746,8 +936,56 @@ impl Window<IambInfo> for IambWindow {
             IambWindow::SpaceList(_) => bold_spans("Spaces"),
             IambWindow::VerifyList(_) => bold_spans("Verifications"),
             IambWindow::Welcome(_) => bold_spans("Welcome to iamb"),
+            IambWindow::Source(w) => {
+                let title = store.application.get_room_title(w.room_id());
+                let v = vec![bold_span("Event Source: "), title.into()];
+                Line::from(v)
+            },
             IambWindow::ChatList(_) => bold_spans("DMs & Rooms"),
             IambWindow::UnreadList(_) => bold_spans("Unread Messages"),
+            IambWindow::SearchResults(state) => {
+                let term = store.application.search.term.as_str();
+                let n = state.len();
+                let v = vec![
+                    bold_span("Search Results "),
+                    Span::styled(format!("({n}): "), bold_style()),
+                    Span::raw(format!("{term:?}")),
+                ];
+                Line::from(v)
+            },
+            IambWindow::Directory(state) => {
+                let directory = &store.application.directory;
+                let server = match &directory.server {
+                    Some(server) => server.to_string(),
+                    None => store.application.settings.profile.user_id.server_name().to_string(),
+                };
+                let n = state.len();
+                let more = if directory.next_batch.is_some() {
+                    "+"
+                } else {
+                    ""
+                };
+                let mut v = vec![
+                    bold_span("Room Directory "),
+                    Span::styled(format!("({n}{more}): "), bold_style()),
+                    Span::raw(server),
+                ];
+
+                if let Some(filter) = &directory.filter {
+                    v.push(Span::raw(format!(" {filter:?}")));
+                }
+
+                Line::from(v)
+            },
+
+            IambWindow::DeviceList(state) => {
+                let n = state.len();
+                let v = vec![
+                    bold_span("Devices "),
+                    Span::styled(format!("({n})"), bold_style()),
+                ];
+                Line::from(v)
+            },
 
             IambWindow::Room(w) => w.get_title(store),
             IambWindow::MemberList(state, room_id, _) => {
@@ -760,6 +998,16 @@ impl Window<IambInfo> for IambWindow {
                 ];
                 Line::from(v)
             },
+            IambWindow::PinnedList(state, room_id) => {
+                let title = store.application.get_room_title(room_id.as_ref());
+                let n = state.len();
+                let v = vec![
+                    bold_span("Pinned Messages "),
+                    Span::styled(format!("({n}): "), bold_style()),
+                    title.into(),
+                ];
+                Line::from(v)
+            },
         }
     }
 
@@ -784,6 +1032,13 @@ impl Window<IambInfo> for IambWindow {
 
                 return Ok(win);
             },
+            IambId::PinnedList(room_id) => {
+                let id = IambBufferId::PinnedList(room_id.clone());
+                let list = PinnedListState::new(id, vec![]);
+                let win = IambWindow::PinnedList(list, room_id);
+
+                return Ok(win);
+            },
             IambId::RoomList => {
                 let list = RoomListState::new(IambBufferId::RoomList, vec![]);
 
@@ -804,6 +1059,11 @@ impl Window<IambInfo> for IambWindow {
 
                 return Ok(win.into());
             },
+            IambId::Source(room_id, event_id) => {
+                let win = SourceState::new(room_id, event_id, store);
+
+                return Ok(win.into());
+            },
             IambId::ChatList => {
                 let list = ChatListState::new(IambBufferId::ChatList, vec![]);
 
@@ -814,6 +1074,23 @@ impl Window<IambInfo> for IambWindow {
 
                 Ok(IambWindow::UnreadList(list))
             },
+            IambId::SearchResults => {
+                let list = SearchResultsState::new(IambBufferId::SearchResults, vec![]);
+
+                Ok(list.into())
+            },
+            IambId::Directory => {
+                let list = DirectoryState::new(IambBufferId::Directory, vec![]);
+
+                Ok(list.into())
+            },
+            IambId::DeviceList => {
+                // The window can be opened without running :devices, like from a saved layout.
+                store.application.worker.refresh_devices();
+                let list = DeviceListState::new(IambBufferId::DeviceList, vec![]);
+
+                Ok(list.into())
+            },
         }
     }
 
@@ -1133,11 +1410,22 @@ impl Display for DirectItem {
 }
 
 impl ListItem<IambInfo> for DirectItem {
-    fn show(&self, selected: bool, _: &ViewportContext<ListCursor>, _: &mut ProgramStore) -> Text {
+    fn show(
+        &self,
+        selected: bool,
+        _: &ViewportContext<ListCursor>,
+        store: &mut ProgramStore,
+    ) -> Text {
         let unread = self.unread.is_unread();
         let style = selected_style(selected);
         let (name, mut labels) = name_and_labels(&self.name, unread, style);
-        let mut spans = vec![name];
+        let presence = self
+            .room()
+            .direct_targets()
+            .iter()
+            .find_map(|user_id| store.application.presences.get(user_id));
+        let mut spans = presence_span(presence).into_iter().collect::<Vec<_>>();
+        spans.push(name);
 
         if let Some(tags) = &self.tags() {
             labels.extend(tags.keys().map(|t| tag_to_span(t, style)));
@@ -1485,7 +1773,8 @@ impl ListItem<IambInfo> for MemberItem {
             style = style.add_modifier(StyleModifier::REVERSED);
         }
 
-        let mut spans = vec![];
+        let presence = store.application.presences.get(user_id);
+        let mut spans = presence_span(presence).into_iter().collect::<Vec<_>>();
         let mut parens = false;
 
         if let Some(name) = name {
@@ -1500,6 +1789,14 @@ impl ListItem<IambInfo> for MemberItem {
         spans.push(Span::styled(user_id.as_str(), style));
         spans.extend(parens.then_some(Span::styled(")", style)));
 
+        let badge = match self.member.power_level() {
+            level if level >= 100 => Span::styled(" [admin]", bold_style()).into(),
+            level if level >= 50 => Span::styled(" [mod]", bold_style()).into(),
+            _ => None,
+        };
+
+        spans.extend(badge);
+
         let state = match self.member.membership() {
             MembershipState::Ban => Span::raw(" (banned)").into(),
             MembershipState::Invite => Span::raw(" (invited)").into(),
@@ -1544,6 +1841,362 @@ impl Promptable<ProgramContext, ProgramStore, IambInfo> for MemberItem {
     }
 }
 
+#[derive(Clone)]
+pub struct PinnedItem {
+    event_id: OwnedEventId,
+    room_id: OwnedRoomId,
+    sender: Option<OwnedUserId>,
+    body: Option<String>,
+}
+
+impl PinnedItem {
+    fn new(event_id: OwnedEventId, room_id: OwnedRoomId, info: &RoomInfo) -> Self {
+        let msg = info.get_event(&event_id);
+        let sender = msg.map(|msg| msg.sender.clone());
+        let body = msg.map(|msg| msg.event.body().into_owned());
+
+        PinnedItem { event_id, room_id, sender, body }
+    }
+}
+
+impl Display for PinnedItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.event_id)
+    }
+}
+
+impl ListItem<IambInfo> for PinnedItem {
+    fn show(
+        &self,
+        selected: bool,
+        _: &ViewportContext<ListCursor>,
+        store: &mut ProgramStore,
+    ) -> Text<'_> {
+        let style = selected_style(selected);
+
+        let (Some(sender), Some(body)) = (&self.sender, &self.body) else {
+            // The message hasn't been loaded into the scrollback yet; selecting it will fetch it.
+            let msg = format!("{} (not loaded yet)", self.event_id);
+
+            return Text::from(Span::styled(msg, style.add_modifier(StyleModifier::ITALIC)));
+        };
+
+        let info = store.application.rooms.get_or_default(self.room_id.clone());
+        let sender = store.application.settings.get_user_span(sender, info);
+        let sender = Span::styled(sender.content.into_owned(), sender.style.patch(style));
+        let snippet = body.lines().next().unwrap_or_default().to_string();
+
+        let spans = vec![
+            sender,
+            Span::styled(": ", style),
+            Span::styled(snippet, style),
+        ];
+
+        Text::from(Line::from(spans))
+    }
+
+    fn get_word(&self) -> Option<String> {
+        self.event_id.to_string().into()
+    }
+}
+
+impl Promptable<ProgramContext, ProgramStore, IambInfo> for PinnedItem {
+    fn prompt(
+        &mut self,
+        act: &PromptAction,
+        ctx: &ProgramContext,
+        _: &mut ProgramStore,
+    ) -> EditResult<Vec<(ProgramAction, ProgramContext)>, IambInfo> {
+        match act {
+            PromptAction::Submit => {
+                let act = IambAction::JumpToEvent(self.room_id.clone(), self.event_id.clone());
+
+                Ok(vec![(act.into(), ctx.clone())])
+            },
+            PromptAction::Abort(_) => {
+                let msg = "Cannot abort entry inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+            PromptAction::Recall(..) => {
+                let msg = "Cannot recall history inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SearchItem {
+    result: SearchResult,
+    room_name: String,
+}
+
+impl SearchItem {
+    fn new(result: SearchResult, store: &mut ProgramStore) -> Self {
+        let room_name = store.application.get_room_title(&result.room_id);
+
+        SearchItem { result, room_name }
+    }
+}
+
+impl Display for SearchItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.result.body)
+    }
+}
+
+impl ListItem<IambInfo> for SearchItem {
+    fn show(
+        &self,
+        selected: bool,
+        _: &ViewportContext<ListCursor>,
+        store: &mut ProgramStore,
+    ) -> Text<'_> {
+        let style = selected_style(selected);
+        let info = store.application.rooms.get_or_default(self.result.room_id.clone());
+        let sender = store.application.settings.get_user_span(&self.result.sender, info);
+        let sender = Span::styled(sender.content.into_owned(), sender.style.patch(style));
+
+        let snippet = self.result.body.lines().next().unwrap_or_default().to_string();
+        let room = format!("[{}] ", self.room_name);
+
+        let spans = vec![
+            Span::styled(room, style.add_modifier(StyleModifier::BOLD)),
+            sender,
+            Span::styled(": ", style),
+            Span::styled(snippet, style),
+        ];
+
+        Text::from(Line::from(spans))
+    }
+
+    fn get_word(&self) -> Option<String> {
+        self.result.event_id.to_string().into()
+    }
+}
+
+impl Promptable<ProgramContext, ProgramStore, IambInfo> for SearchItem {
+    fn prompt(
+        &mut self,
+        act: &PromptAction,
+        ctx: &ProgramContext,
+        _: &mut ProgramStore,
+    ) -> EditResult<Vec<(ProgramAction, ProgramContext)>, IambInfo> {
+        match act {
+            PromptAction::Submit => {
+                let room_id = self.result.room_id.clone();
+                let event_id = self.result.event_id.clone();
+                let act = IambAction::JumpToEvent(room_id, event_id);
+
+                Ok(vec![(act.into(), ctx.clone())])
+            },
+            PromptAction::Abort(_) => {
+                let msg = "Cannot abort entry inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+            PromptAction::Recall(..) => {
+                let msg = "Cannot recall history inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DirectoryItem {
+    room: PublicRoomsChunk,
+}
+
+impl From<PublicRoomsChunk> for DirectoryItem {
+    fn from(room: PublicRoomsChunk) -> Self {
+        DirectoryItem { room }
+    }
+}
+
+impl Display for DirectoryItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.room.name, &self.room.canonical_alias) {
+            (Some(name), _) => write!(f, "{name}"),
+            (None, Some(alias)) => write!(f, "{alias}"),
+            (None, None) => write!(f, "{}", self.room.room_id),
+        }
+    }
+}
+
+impl ListItem<IambInfo> for DirectoryItem {
+    fn show(
+        &self,
+        selected: bool,
+        _: &ViewportContext<ListCursor>,
+        _: &mut ProgramStore,
+    ) -> Text<'_> {
+        let style = selected_style(selected);
+        let name = Span::styled(self.to_string(), style.add_modifier(StyleModifier::BOLD));
+        let mut spans = vec![name];
+
+        if let (Some(_), Some(alias)) = (&self.room.name, &self.room.canonical_alias) {
+            spans.push(Span::styled(format!(" ({alias})"), style));
+        }
+
+        let members = format!(" [{} members]", self.room.num_joined_members);
+        spans.push(Span::styled(members, style));
+
+        if let Some(topic) = &self.room.topic {
+            let topic = topic.lines().next().unwrap_or_default().to_string();
+            spans.push(Span::styled(": ", style));
+            spans.push(Span::styled(topic, style));
+        }
+
+        Text::from(Line::from(spans))
+    }
+
+    fn get_word(&self) -> Option<String> {
+        match &self.room.canonical_alias {
+            Some(alias) => alias.to_string(),
+            None => self.room.room_id.to_string(),
+        }
+        .into()
+    }
+}
+
+impl Promptable<ProgramContext, ProgramStore, IambInfo> for DirectoryItem {
+    fn prompt(
+        &mut self,
+        act: &PromptAction,
+        ctx: &ProgramContext,
+        store: &mut ProgramStore,
+    ) -> EditResult<Vec<(ProgramAction, ProgramContext)>, IambInfo> {
+        match act {
+            PromptAction::Submit => {
+                // Ask the server whose directory listed the room, since ours may not be in it.
+                let room_id = self.room.room_id.clone();
+                let servers = vec![
+                    store.application.directory.server.clone(),
+                    room_id.server_name().map(ServerName::to_owned),
+                ]
+                .into_iter()
+                .flatten()
+                .collect();
+                let act = IambAction::from(HomeserverAction::JoinRoom(room_id, servers));
+
+                Ok(vec![(act.into(), ctx.clone())])
+            },
+            PromptAction::Abort(_) => {
+                let msg = "Cannot abort entry inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+            PromptAction::Recall(..) => {
+                let msg = "Cannot recall history inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct DeviceItem {
+    entry: DeviceEntry,
+}
+
+impl From<DeviceEntry> for DeviceItem {
+    fn from(entry: DeviceEntry) -> Self {
+        DeviceItem { entry }
+    }
+}
+
+impl Display for DeviceItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let device = &self.entry.device;
+
+        match &device.display_name {
+            Some(name) => write!(f, "{name} ({})", device.device_id),
+            None => write!(f, "{}", device.device_id),
+        }
+    }
+}
+
+impl ListItem<IambInfo> for DeviceItem {
+    fn show(
+        &self,
+        selected: bool,
+        _: &ViewportContext<ListCursor>,
+        _: &mut ProgramStore,
+    ) -> Text<'_> {
+        let style = selected_style(selected);
+        let device = &self.entry.device;
+        let name = Span::styled(self.to_string(), style.add_modifier(StyleModifier::BOLD));
+        let mut spans = vec![name];
+
+        if self.entry.current {
+            spans.push(Span::styled(" [this device]", style));
+        }
+
+        let (state, color) = if self.entry.verified {
+            (" verified", Color::Green)
+        } else {
+            (" unverified", Color::Red)
+        };
+        spans.push(Span::styled(state, style.fg(color)));
+
+        let seen = match (&device.last_seen_ip, device.last_seen_ts) {
+            (Some(ip), Some(ts)) => format!(", last seen from {ip} on {}", format_seen(ts.0)),
+            (Some(ip), None) => format!(", last seen from {ip}"),
+            (None, Some(ts)) => format!(", last seen on {}", format_seen(ts.0)),
+            (None, None) => String::new(),
+        };
+        spans.push(Span::styled(seen, style));
+
+        Text::from(Line::from(spans))
+    }
+
+    fn get_word(&self) -> Option<String> {
+        self.entry.device.device_id.to_string().into()
+    }
+}
+
+impl Promptable<ProgramContext, ProgramStore, IambInfo> for DeviceItem {
+    fn prompt(
+        &mut self,
+        act: &PromptAction,
+        _: &ProgramContext,
+        _: &mut ProgramStore,
+    ) -> EditResult<Vec<(ProgramAction, ProgramContext)>, IambInfo> {
+        match act {
+            PromptAction::Submit => Ok(vec![]),
+            PromptAction::Abort(_) => {
+                let msg = "Cannot abort entry inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+            PromptAction::Recall(..) => {
+                let msg = "Cannot recall history inside a list";
+                let err = EditError::Failure(msg.into());
+
+                Err(err)
+            },
+        }
+    }
+}
+
+fn format_seen(ms: UInt) -> String {
+    let time = DateTime::from_timestamp_millis(i64::from(ms)).unwrap_or_default();
+
+    time.with_timezone(&LocalTz).format("%Y-%m-%d %H:%M").to_string()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,5 +1,5 @@
 //! Window for Matrix rooms
-use std::borrow::Cow;
+use std::convert::TryFrom;
 use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::ops::Deref;
@@ -17,6 +17,20 @@ use matrix_sdk::{
     media::{MediaFormat, MediaRequestParameters},
     room::Room as MatrixRoom,
     ruma::{
+        events::poll::{
+            end::PollEndEventContent,
+            response::PollResponseEventContent,
+            start::PollAnswers,
+            unstable_end::UnstablePollEndEventContent,
+            unstable_response::UnstablePollResponseEventContent,
+            unstable_start::{
+                NewUnstablePollStartEventContent,
+                UnstablePollAnswer,
+                UnstablePollAnswers,
+                UnstablePollStartContentBlock,
+                UnstablePollStartEventContent,
+            },
+        },
         events::reaction::ReactionEventContent,
         events::relation::{Annotation, Replacement},
         events::room::message::{
@@ -29,6 +43,8 @@ use matrix_sdk::{
             RoomMessageEventContent,
             TextMessageEventContent,
         },
+        events::room::pinned_events::RoomPinnedEventsEventContent,
+        EventId,
         OwnedEventId,
         OwnedRoomId,
         RoomId,
@@ -39,6 +55,7 @@ use matrix_sdk::{
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
+    style::{Color, Style},
     text::{Line, Span},
     widgets::{Paragraph, StatefulWidget, Widget},
 };
@@ -60,6 +77,7 @@ use modalkit::actions::{
     PromptAction,
     Promptable,
     Scrollable,
+    WindowAction,
 };
 use modalkit::editing::{
     completion::CompletionList,
@@ -75,9 +93,14 @@ use crate::base::{
     IambAction,
     IambBufferId,
     IambError,
+    IambId,
     IambInfo,
     IambResult,
+    IdentityAction,
+    KeysAction,
     MessageAction,
+    Poll,
+    PollStart,
     ProgramAction,
     ProgramContext,
     ProgramStore,
@@ -87,7 +110,7 @@ use crate::base::{
 };
 
 use crate::message::{text_to_message, Message, MessageEvent, MessageKey, MessageTimeStamp};
-use crate::worker::Requester;
+use crate::worker::{get_event_source, read_upload, unverified_devices, Requester};
 
 use super::scrollback::{Scrollback, ScrollbackState};
 
@@ -174,9 +197,9 @@ impl ChatState {
     pub async fn message_command(
         &mut self,
         act: MessageAction,
-        _: ProgramContext,
+        ctx: ProgramContext,
         store: &mut ProgramStore,
-    ) -> IambResult<EditInfo> {
+    ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
         let client = &store.application.worker.client;
 
         let settings = &store.application.settings;
@@ -189,7 +212,7 @@ impl ChatState {
                 if skip_confirm {
                     self.reset();
 
-                    return Ok(None);
+                    return Ok(vec![]);
                 }
 
                 self.reply_to = None;
@@ -317,7 +340,9 @@ impl ChatState {
                         ))
                     };
 
-                    return Ok(info.into());
+                    let act = Action::ShowInfoMessage(info);
+
+                    return Ok(vec![(act, ctx)]);
                 }
 
                 Err(IambError::NoAttachment.into())
@@ -356,7 +381,118 @@ impl ChatState {
                 self.editing = self.scrollback.get_key(info);
                 self.focus = RoomFocus::MessageBar;
 
-                Ok(None)
+                Ok(vec![])
+            },
+            MessageAction::Encryption => {
+                let msg = msg.encryption_details();
+                let act = Action::ShowInfoMessage(InfoMessage::Pager(msg));
+
+                Ok(vec![(act, ctx)])
+            },
+            MessageAction::DownloadBackupKey => {
+                let Some(session_id) = msg.event.megolm_session_id() else {
+                    let msg = "Selected message is not an undecryptable message";
+                    return Err(UIError::Failure(msg.into()));
+                };
+                let session_id = session_id.to_owned();
+
+                let found = client
+                    .encryption()
+                    .backups()
+                    .download_room_key(&self.room_id, &session_id)
+                    .await
+                    .map_err(IambError::from)?;
+
+                if !found {
+                    let msg = "Key backup is not enabled on this device (see :keys backup restore)";
+                    return Err(UIError::Failure(msg.into()));
+                }
+
+                let retry = KeysAction::Retry(self.room_id.clone(), Some(session_id));
+                let retry = Action::from(IambAction::Keys(retry));
+                let info = Action::ShowInfoMessage("Downloaded room key from key backup".into());
+
+                Ok(vec![(retry, ctx.clone()), (info, ctx)])
+            },
+            MessageAction::Pin => {
+                let room = self.get_joined(&store.application.worker)?;
+                let event_id = match &msg.event {
+                    MessageEvent::EncryptedOriginal(ev) => ev.event_id.clone(),
+                    MessageEvent::EncryptedRedacted(ev) => ev.event_id.clone(),
+                    MessageEvent::Original(ev) => ev.event_id.clone(),
+                    MessageEvent::Local(event_id, _) => event_id.clone(),
+                    MessageEvent::Redacted(_) => {
+                        let msg = "Cannot pin a redacted message";
+                        let err = UIError::Failure(msg.into());
+
+                        return Err(err);
+                    },
+                };
+
+                let mut pinned = room.pinned_event_ids().unwrap_or_default();
+
+                if pinned.contains(&event_id) {
+                    let msg = "This message is already pinned";
+                    let err = UIError::Failure(msg.into());
+
+                    return Err(err);
+                }
+
+                pinned.push(event_id);
+
+                let ev = RoomPinnedEventsEventContent::new(pinned);
+                let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
+            MessageAction::PollEnd => {
+                let room = self.get_joined(&store.application.worker)?;
+                let event_id = msg.event.event_id().to_owned();
+                let (poll, start) = selected_poll(info, &event_id)?;
+
+                if start.sender != settings.profile.user_id {
+                    let msg = "Only the user who started a poll can end it";
+                    let err = UIError::Failure(msg.into());
+
+                    return Err(err);
+                }
+
+                let text = poll.results_text();
+
+                if start.stable {
+                    let content = PollEndEventContent::with_plain_text(text, event_id);
+                    let _ = room.send(content).await.map_err(IambError::from)?;
+                } else {
+                    let content = UnstablePollEndEventContent::new(text, event_id);
+                    let _ = room.send(content).await.map_err(IambError::from)?;
+                }
+
+                Ok(vec![])
+            },
+            MessageAction::PollVote(answer) => {
+                let room = self.get_joined(&store.application.worker)?;
+                let event_id = msg.event.event_id().to_owned();
+                let (_, start) = selected_poll(info, &event_id)?;
+
+                let Some((answer, _)) = answer.checked_sub(1).and_then(|i| start.answers.get(i))
+                else {
+                    let msg = format!("Answer must be between 1 and {}", start.answers.len());
+                    let err = UIError::Failure(msg);
+
+                    return Err(err);
+                };
+
+                let selections = vec![answer.clone()];
+
+                if start.stable {
+                    let content = PollResponseEventContent::new(selections.into(), event_id);
+                    let _ = room.send(content).await.map_err(IambError::from)?;
+                } else {
+                    let content = UnstablePollResponseEventContent::new(selections, event_id);
+                    let _ = room.send(content).await.map_err(IambError::from)?;
+                }
+
+                Ok(vec![])
             },
             MessageAction::React(reaction, literal) => {
                 let emoji = if literal {
@@ -399,7 +535,7 @@ impl ChatState {
                 let msg = ReactionEventContent::new(reaction);
                 let _ = room.send(msg).await.map_err(IambError::from)?;
 
-                Ok(None)
+                Ok(vec![])
             },
             MessageAction::Redact(reason, skip_confirm) => {
                 if !skip_confirm {
@@ -429,13 +565,28 @@ impl ChatState {
                 let reason = reason.as_deref();
                 let _ = room.redact(event_id, reason, None).await.map_err(IambError::from)?;
 
-                Ok(None)
+                Ok(vec![])
             },
             MessageAction::Reply => {
                 self.reply_to = self.scrollback.get_key(info);
                 self.focus = RoomFocus::MessageBar;
 
-                Ok(None)
+                Ok(vec![])
+            },
+            MessageAction::Source => {
+                let room_id = self.room_id.clone();
+                let event_id = msg.event.event_id().to_owned();
+                let source = get_event_source(client, &room_id, &event_id).await?;
+
+                let id = IambBufferId::Source(room_id.clone(), event_id.clone());
+                if let Ok(mut buf) = store.load_buffer(id).write() {
+                    buf.set_text(source);
+                }
+
+                let target = OpenTarget::Application(IambId::Source(room_id, event_id));
+                let act = WindowAction::Switch(target);
+
+                Ok(vec![(act.into(), ctx)])
             },
             MessageAction::Unreact(reaction, literal) => {
                 let emoji = match reaction {
@@ -474,7 +625,7 @@ impl ChatState {
 
                 let reactions = match info.reactions.get(&event_id) {
                     Some(r) => r,
-                    None => return Ok(None),
+                    None => return Ok(vec![]),
                 };
 
                 let reactions = reactions.iter().filter_map(|(event_id, (reaction, user_id))| {
@@ -497,7 +648,26 @@ impl ChatState {
                     let _ = room.redact(reaction, None, None).await.map_err(IambError::from)?;
                 }
 
-                Ok(None)
+                Ok(vec![])
+            },
+            MessageAction::Unpin => {
+                let room = self.get_joined(&store.application.worker)?;
+                let event_id = msg.event.event_id();
+                let mut pinned = room.pinned_event_ids().unwrap_or_default();
+
+                if !pinned.iter().any(|id| id == event_id) {
+                    let msg = "This message isn't pinned";
+                    let err = UIError::Failure(msg.into());
+
+                    return Err(err);
+                }
+
+                pinned.retain(|id| id != event_id);
+
+                let ev = RoomPinnedEventsEventContent::new(pinned);
+                let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
+
+                Ok(vec![])
             },
         }
     }
@@ -509,6 +679,44 @@ impl ChatState {
         store: &mut ProgramStore,
     ) -> IambResult<EditInfo> {
         let room = self.get_joined(&store.application.worker)?;
+
+        if let Some(info) = store.application.rooms.get(self.id()) {
+            if !info.identity_violations.is_empty() {
+                let mut users = info.identity_violations.iter().cloned().collect::<Vec<_>>();
+                users.sort();
+
+                let names = users.iter().map(|u| u.as_str()).collect::<Vec<_>>().join(", ");
+                let msg = format!(
+                    "The identity of {names} has changed since you verified them. \
+                     Stop treating them as verified and send anyway?"
+                );
+
+                let mut acts = users
+                    .into_iter()
+                    .map(|user| Action::from(IambAction::from(IdentityAction::Withdraw(user))))
+                    .collect::<Vec<_>>();
+                acts.push(Action::from(IambAction::Send(act)));
+
+                let prompt = PromptYesNo::new(msg, acts);
+                let prompt = Box::new(prompt);
+
+                return Err(UIError::NeedConfirm(prompt));
+            }
+        }
+
+        if store.application.settings.tunables.refuse_unverified_devices {
+            let devices = unverified_devices(&store.application.worker.client, &room).await?;
+
+            if !devices.is_empty() {
+                let msg = format!(
+                    "Refusing to send to a room with unverified devices: {}",
+                    devices.join(", ")
+                );
+
+                return Err(UIError::Failure(msg));
+            }
+        }
+
         let info = store.application.rooms.get_or_default(self.id().to_owned());
         let mut show_echo = true;
 
@@ -565,18 +773,11 @@ impl ChatState {
                 (event_id, msg)
             },
             SendAction::Upload(file) => {
-                let path = Path::new(file.as_str());
-                let mime = mime_guess::from_path(path).first_or(mime::APPLICATION_OCTET_STREAM);
-
-                let bytes = fs::read(path)?;
-                let name = path
-                    .file_name()
-                    .map(OsStr::to_string_lossy)
-                    .unwrap_or_else(|| Cow::from("Attachment"));
+                let (name, mime, bytes) = read_upload(Path::new(file.as_str()))?;
                 let config = AttachmentConfig::new();
 
                 let resp = room
-                    .send_attachment(name.as_ref(), &mime, bytes, config)
+                    .send_attachment(name.as_str(), &mime, bytes, config)
                     .await
                     .map_err(IambError::from)?;
 
@@ -617,6 +818,37 @@ impl ChatState {
 
                 (resp.event_id, msg)
             },
+            SendAction::Poll(question, answers) => {
+                let mut fallback = question.clone();
+
+                for (idx, answer) in answers.iter().enumerate() {
+                    fallback.push_str(&format!("\n{}. {answer}", idx + 1));
+                }
+
+                let answers = answers
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, answer)| UnstablePollAnswer::new(idx.to_string(), answer))
+                    .collect::<Vec<_>>();
+                let Ok(answers) = UnstablePollAnswers::try_from(answers) else {
+                    let max = PollAnswers::MAX_LENGTH;
+                    let msg = format!("A poll can have at most {max} answers");
+                    let err = UIError::Failure(msg);
+
+                    return Err(err);
+                };
+
+                let poll = UnstablePollStartContentBlock::new(question, answers);
+                let content = NewUnstablePollStartEventContent::plain_text(fallback, poll);
+                let content = UnstablePollStartEventContent::from(content);
+                let _ = room.send(content).await.map_err(IambError::from)?;
+
+                // There's no local echo for polls, since their answers and votes are tracked
+                // separately from the message; the poll shows up once the server sends it back.
+                self.scrollback.goto_latest();
+
+                return Ok(None);
+            },
         };
 
         if show_echo {
@@ -634,6 +866,12 @@ impl ChatState {
         Ok(None)
     }
 
+    /// Select a message in the scrollback.
+    pub fn goto_message(&mut self, key: MessageKey) {
+        self.focus = RoomFocus::Scrollback;
+        self.scrollback.goto_message(key);
+    }
+
     pub fn focus_toggle(&mut self) {
         self.focus = match self.focus {
             RoomFocus::Scrollback => RoomFocus::MessageBar,
@@ -912,7 +1150,9 @@ impl<'a> StatefulWidget for Chat<'a> {
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         // Determine whether we have a description to show for the message bar.
         let desc_spans = match (&state.editing, &state.reply_to, state.thread()) {
-            (None, None, None) => None,
+            (None, None, None) => {
+                self.store.application.rooms.get(state.id()).and_then(identity_warning)
+            },
             (None, None, Some(_)) => Some(Line::from("Replying in thread")),
             (Some(_), None, None) => Some(Line::from("Editing message")),
             (Some(_), None, Some(_)) => Some(Line::from("Editing message in thread")),
@@ -968,6 +1208,39 @@ impl<'a> StatefulWidget for Chat<'a> {
     }
 }
 
+/// Get the poll started by an event, failing if it isn't one, or if the poll has ended.
+fn selected_poll<'a>(
+    info: &'a RoomInfo,
+    event_id: &EventId,
+) -> IambResult<(&'a Poll, &'a PollStart)> {
+    let Some((poll, Some(start))) = info.polls.get(event_id).map(|poll| (poll, &poll.start)) else {
+        let msg = "The selected message is not a poll";
+        let err = UIError::Failure(msg.into());
+
+        return Err(err);
+    };
+
+    if poll.ended.is_some() {
+        let msg = "This poll has already ended";
+        let err = UIError::Failure(msg.into());
+
+        return Err(err);
+    }
+
+    return Ok((poll, start));
+}
+
+/// Warn about members whose identity has changed since they were verified.
+fn identity_warning(info: &RoomInfo) -> Option<Line<'static>> {
+    let user = info.identity_violations.iter().min()?;
+    let style = Style::default().fg(Color::Red);
+    let msg = format!(
+        "\u{26A0} {user}'s identity has changed since you verified them (see :whois {user})"
+    );
+
+    return Some(Line::from(Span::styled(msg, style)));
+}
+
 fn open_command(open_command: Option<&Vec<String>>, target: OsString) -> IambResult<()> {
     if let Some(mut cmd) = open_command.and_then(cmd) {
         cmd.arg(target);
@@ -2,6 +2,7 @@
 use std::collections::HashSet;
 
 use matrix_sdk::{
+    deserialized_responses::SyncOrStrippedState,
     notification_settings::RoomNotificationMode,
     room::Room as MatrixRoom,
     ruma::{
@@ -16,16 +17,28 @@ use matrix_sdk::{
             room::{
                 canonical_alias::RoomCanonicalAliasEventContent,
                 history_visibility::{HistoryVisibility, RoomHistoryVisibilityEventContent},
+                join_rules::{AllowRule, JoinRule, Restricted, RoomJoinRulesEventContent},
+                member::{MembershipState, RoomMemberEventContent},
                 name::RoomNameEventContent,
+                power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
+                server_acl::RoomServerAclEventContent,
                 topic::RoomTopicEventContent,
             },
+            space::{child::SpaceChildEventContent, parent::SpaceParentEventContent},
             tag::{TagInfo, Tags},
+            SyncStateEvent,
         },
+        serde::Raw,
+        Int,
         OwnedEventId,
         OwnedRoomAliasId,
         OwnedUserId,
         RoomId,
+        RoomOrAliasId,
+        ServerName,
+        UserId,
     },
+    Client,
     RoomDisplayName,
     RoomState as MatrixRoomState,
 };
@@ -46,6 +59,7 @@ use modalkit::actions::{
     PromptAction,
     Promptable,
     Scrollable,
+    WindowAction,
 };
 use modalkit::errors::{EditResult, UIError};
 use modalkit::prelude::*;
@@ -53,6 +67,7 @@ use modalkit::{editing::completion::CompletionList, keybindings::dialog::PromptY
 use modalkit_ratatui::{TermOffset, TerminalCursor, WindowOps};
 
 use crate::base::{
+    FoldAction,
     IambAction,
     IambError,
     IambId,
@@ -63,10 +78,16 @@ use crate::base::{
     ProgramAction,
     ProgramContext,
     ProgramStore,
+    PurgeLimit,
+    PurgeProgress,
+    RawJsonAction,
     RoomAction,
     RoomField,
     SendAction,
+    ServerAclAction,
 };
+use crate::message::MessageKey;
+use crate::worker::{edit_json, get_state_content, get_state_source, resolve_room, via_servers};
 
 use self::chat::ChatState;
 use self::space::{Space, SpaceState};
@@ -99,6 +120,103 @@ fn notification_mode(name: impl Into<String>) -> IambResult<RoomNotificationMode
     Ok(mode)
 }
 
+/// Parse a join rule, followed by the spaces whose members may join for restricted rules.
+async fn join_rule(client: &Client, value: &str) -> IambResult<JoinRule> {
+    let mut args = value.split_whitespace();
+    let name = args.next().unwrap_or_default();
+    let spaces = args.collect::<Vec<_>>();
+
+    let restricted = match name {
+        "public" | "invite" | "knock" => false,
+        "restricted" | "knock_restricted" => true,
+        _ => return Err(IambError::InvalidJoinRule(name.into()).into()),
+    };
+
+    if restricted == spaces.is_empty() {
+        let msg = if restricted {
+            format!("The {name} join rule needs at least one space whose members may join")
+        } else {
+            format!("The {name} join rule doesn't take any spaces")
+        };
+
+        return Err(UIError::Failure(msg));
+    }
+
+    let mut allow = vec![];
+
+    for space in spaces {
+        allow.push(AllowRule::room_membership(resolve_room(client, space).await?));
+    }
+
+    let rule = match name {
+        "public" => JoinRule::Public,
+        "invite" => JoinRule::Invite,
+        "knock" => JoinRule::Knock,
+        "restricted" => JoinRule::Restricted(Restricted::new(allow)),
+        _ => JoinRule::KnockRestricted(Restricted::new(allow)),
+    };
+
+    Ok(rule)
+}
+
+/// Check that a server name pattern only uses the characters allowed in a server ACL.
+fn server_acl_glob(glob: String) -> IambResult<String> {
+    let valid = !glob.is_empty() &&
+        glob.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '*' | '?'));
+
+    if valid {
+        Ok(glob)
+    } else {
+        Err(IambError::InvalidServerAcl(glob).into())
+    }
+}
+
+/// Fetch a room's server ACL, or the default one that lets every server participate.
+async fn server_acl(room: &MatrixRoom) -> IambResult<RoomServerAclEventContent> {
+    let ev = room
+        .get_state_event_static::<RoomServerAclEventContent>()
+        .await
+        .map_err(IambError::from)?
+        .and_then(|ev| ev.deserialize().ok());
+
+    let acl = match ev {
+        Some(SyncOrStrippedState::Sync(SyncStateEvent::Original(ev))) => ev.content,
+        _ => RoomServerAclEventContent::new(true, vec!["*".into()], vec![]),
+    };
+
+    Ok(acl)
+}
+
+/// Change our display name in a room, keeping the rest of our membership the same.
+async fn set_nick(room: &MatrixRoom, user_id: &UserId, nick: Option<String>) -> IambResult<()> {
+    let member = room
+        .get_member_no_sync(user_id)
+        .await
+        .map_err(IambError::from)?
+        .ok_or(IambError::NotJoined)?;
+
+    let mut ev = RoomMemberEventContent::new(MembershipState::Join);
+    ev.displayname = nick;
+    ev.avatar_url = member.avatar_url().map(ToOwned::to_owned);
+
+    let _ = room.send_state_event_for_key(user_id, ev).await.map_err(IambError::from)?;
+
+    Ok(())
+}
+
+async fn room_account_data(room: &MatrixRoom, event_type: &str) -> IambResult<Option<String>> {
+    let Some(ev) = room.account_data(event_type.into()).await.map_err(IambError::from)? else {
+        return Ok(None);
+    };
+
+    let content = ev.get_field::<serde_json::Value>("content").map_err(IambError::from)?;
+    let content =
+        serde_json::to_string_pretty(&content.unwrap_or_default()).map_err(IambError::from)?;
+
+    return Ok(Some(content));
+}
+
 fn hist_visibility_mode(name: impl Into<String>) -> IambResult<HistoryVisibility> {
     let name = name.into();
 
@@ -113,6 +231,29 @@ fn hist_visibility_mode(name: impl Into<String>) -> IambResult<HistoryVisibility
     Ok(mode)
 }
 
+fn power_level(name: impl Into<String>) -> IambResult<Int> {
+    let name = name.into();
+
+    let level = match name.to_lowercase().as_str() {
+        "admin" => Int::from(100),
+        "mod" | "moderator" => Int::from(50),
+        "user" => Int::from(0),
+        s => s.parse().map_err(|_| IambError::InvalidPowerLevel(name))?,
+    };
+
+    Ok(level)
+}
+
+async fn power_levels(room: &MatrixRoom) -> IambResult<RoomPowerLevels> {
+    let levels = room
+        .power_levels()
+        .await
+        .map_err(matrix_sdk::Error::from)
+        .map_err(IambError::from)?;
+
+    Ok(levels)
+}
+
 /// State for a Matrix room or space.
 ///
 /// Since spaces function as special rooms within Matrix, we wrap their window state together, so
@@ -207,7 +348,7 @@ impl RoomState {
         act: MessageAction,
         ctx: ProgramContext,
         store: &mut ProgramStore,
-    ) -> IambResult<EditInfo> {
+    ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
         match self {
             RoomState::Chat(chat) => chat.message_command(act, ctx, store).await,
             RoomState::Space(_) => Err(IambError::NoSelectedMessage.into()),
@@ -233,6 +374,33 @@ impl RoomState {
         store: &mut ProgramStore,
     ) -> IambResult<Vec<(Action<IambInfo>, ProgramContext)>> {
         match act {
+            RoomAction::AccountData(RawJsonAction::Show(ty)) => {
+                let Some(content) = room_account_data(self.room(), &ty).await? else {
+                    let msg = format!("No {ty} account data has been set for this room");
+                    let err = UIError::Failure(msg);
+
+                    return Err(err);
+                };
+
+                let msg = InfoMessage::Pager(content);
+                let act = Action::ShowInfoMessage(msg);
+
+                Ok(vec![(act, ctx)])
+            },
+            RoomAction::AccountData(RawJsonAction::Edit(ty)) => {
+                let room = self.room();
+                let content = room_account_data(room, &ty).await?;
+                let content = content.unwrap_or_else(|| "{}".into());
+
+                let Some(value) = edit_json(&content)? else {
+                    return Ok(vec![]);
+                };
+
+                let raw = Raw::new(&value).map_err(IambError::from)?.cast();
+                let _ = room.set_account_data_raw(ty.into(), raw).await.map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
             RoomAction::InviteAccept => {
                 if let Some(room) = store.application.worker.client.get_room(self.id()) {
                     let details = room.invite_details().await.map_err(IambError::from)?;
@@ -250,6 +418,32 @@ impl RoomState {
                     Err(IambError::NotInvited.into())
                 }
             },
+            RoomAction::EnableEncryption(skip_confirm) => {
+                let room = store
+                    .application
+                    .get_joined_room(self.id())
+                    .ok_or(UIError::Application(IambError::NotJoined))?;
+
+                if room.encryption_settings().is_some() {
+                    let msg = "Encryption is already enabled in this room";
+
+                    return Err(UIError::Failure(msg.into()));
+                }
+
+                if !skip_confirm {
+                    let msg = "Encryption can never be disabled once it's been enabled. \
+                               Do you really want to enable encryption in this room?";
+                    let act = IambAction::Room(RoomAction::EnableEncryption(true));
+                    let prompt = PromptYesNo::new(msg, vec![Action::from(act)]);
+                    let prompt = Box::new(prompt);
+
+                    return Err(UIError::NeedConfirm(prompt));
+                }
+
+                room.enable_encryption().await.map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
             RoomAction::InviteReject => {
                 if let Some(room) = store.application.worker.client.get_room(self.id()) {
                     room.leave().await.map_err(IambError::from)?;
@@ -268,6 +462,37 @@ impl RoomState {
                     Err(IambError::NotJoined.into())
                 }
             },
+            RoomAction::JoinSuccessor => {
+                let Some(tombstone) = self.room().tombstone() else {
+                    let msg = "This room hasn't been replaced by a newer room";
+                    let err = UIError::Failure(msg.into());
+
+                    return Err(err);
+                };
+
+                let new_room = tombstone.replacement_room;
+
+                // Ask the servers of both rooms, since ours might not be in the new room yet.
+                let servers = vec![new_room.server_name(), self.id().server_name()]
+                    .into_iter()
+                    .flatten()
+                    .map(ServerName::to_owned)
+                    .collect::<Vec<_>>();
+
+                let room_or_alias = <&RoomOrAliasId>::from(&*new_room);
+                let _ = store
+                    .application
+                    .worker
+                    .client
+                    .join_room_by_id_or_alias(room_or_alias, &servers)
+                    .await
+                    .map_err(IambError::from)?;
+
+                let open = IambId::Room(new_room, None);
+                let open = WindowAction::Switch(OpenTarget::Application(open));
+
+                Ok(vec![(open.into(), ctx)])
+            },
             RoomAction::Leave(skip_confirm) => {
                 if let Some(room) = store.application.worker.client.get_room(self.id()) {
                     if skip_confirm {
@@ -297,8 +522,28 @@ impl RoomState {
                     return Err(err.into());
                 };
 
+                if matches!(mua, MemberUpdateAction::KnockAccept | MemberUpdateAction::KnockDeny) {
+                    let knocked = room
+                        .get_member_no_sync(&user_id)
+                        .await
+                        .map_err(IambError::from)?
+                        .is_some_and(|member| *member.membership() == MembershipState::Knock);
+
+                    if !knocked {
+                        let msg = format!("{user} hasn't asked to join this room");
+                        let err = UIError::Failure(msg);
+
+                        return Err(err);
+                    }
+                }
+
                 if !skip_confirm {
-                    let msg = format!("Do you really want to {mua} {user} from this room?");
+                    let msg = match mua {
+                        MemberUpdateAction::KnockAccept | MemberUpdateAction::KnockDeny => {
+                            format!("Do you really want to {mua} {user}?")
+                        },
+                        _ => format!("Do you really want to {mua} {user} from this room?"),
+                    };
                     let act = RoomAction::MemberUpdate(mua, user, reason, true);
                     let act = IambAction::from(act);
                     let prompt = PromptYesNo::new(msg, vec![Action::from(act)]);
@@ -323,6 +568,15 @@ impl RoomState {
                             .await
                             .map_err(IambError::from)?;
                     },
+                    MemberUpdateAction::KnockAccept => {
+                        room.invite_user_by_id(&user_id).await.map_err(IambError::from)?;
+                    },
+                    MemberUpdateAction::KnockDeny => {
+                        // Kicking a knocking user rejects their request to join.
+                        room.kick_user(&user_id, reason.as_deref())
+                            .await
+                            .map_err(IambError::from)?;
+                    },
                 }
 
                 Ok(vec![])
@@ -337,6 +591,178 @@ impl RoomState {
 
                 Ok(vec![(act, cmd.context.clone())])
             },
+            RoomAction::Pins(mut cmd) => {
+                let width = Count::Exact(50);
+                let act =
+                    cmd.default_axis(Axis::Vertical).default_relation(MoveDir1D::Next).window(
+                        OpenTarget::Application(IambId::PinnedList(self.id().to_owned())),
+                        width.into(),
+                    );
+
+                Ok(vec![(act, cmd.context.clone())])
+            },
+            RoomAction::Purge(user, limit, reason, skip_confirm) => {
+                if store.application.get_joined_room(self.id()).is_none() {
+                    return Err(IambError::NotJoined.into());
+                }
+
+                let Ok(user_id) = OwnedUserId::try_from(user.as_str()) else {
+                    let err = IambError::InvalidUserId(user);
+
+                    return Err(err.into());
+                };
+
+                let info = store.application.rooms.get_or_default(self.id().to_owned());
+
+                if info.purge.is_some() {
+                    let msg = "Already redacting messages in this room";
+
+                    return Err(UIError::Failure(msg.into()));
+                }
+
+                if !skip_confirm {
+                    let which = match &limit {
+                        PurgeLimit::Count(n) => format!("the last {n}"),
+                        PurgeLimit::Since(_) => "the recent".into(),
+                    };
+                    let msg = format!("Do you really want to redact {which} messages from {user}?");
+                    let act = RoomAction::Purge(user, limit, reason, true);
+                    let act = IambAction::from(act);
+                    let prompt = PromptYesNo::new(msg, vec![Action::from(act)]);
+                    let prompt = Box::new(prompt);
+
+                    return Err(UIError::NeedConfirm(prompt));
+                }
+
+                info.purge = Some(PurgeProgress { scanning: true, ..Default::default() });
+                store
+                    .application
+                    .worker
+                    .purge(self.id().to_owned(), user_id, limit, reason);
+
+                Ok(vec![])
+            },
+            RoomAction::ServerAcl(action, glob, skip_confirm) => {
+                let room = store
+                    .application
+                    .get_joined_room(self.id())
+                    .ok_or(UIError::Application(IambError::NotJoined))?;
+
+                let glob = server_acl_glob(glob)?;
+                let mut acl = server_acl(&room).await?;
+
+                match action {
+                    ServerAclAction::Allow => {
+                        if acl.deny.contains(&glob) {
+                            acl.deny.retain(|g| g != &glob);
+                        } else if !acl.allow.contains(&glob) {
+                            acl.allow.push(glob.clone());
+                        }
+                    },
+                    ServerAclAction::Deny => {
+                        acl.allow.retain(|g| g != &glob);
+
+                        if !acl.deny.contains(&glob) {
+                            acl.deny.push(glob.clone());
+                        }
+                    },
+                }
+
+                let own = store.application.settings.profile.user_id.server_name();
+
+                if !skip_confirm && !acl.is_allowed(own) {
+                    let msg = format!(
+                        "This will stop your own server ({own}) from participating in this room. \
+                         Do you really want to change the server ACL?"
+                    );
+                    let act = RoomAction::ServerAcl(action, glob, true);
+                    let act = IambAction::from(act);
+                    let prompt = PromptYesNo::new(msg, vec![Action::from(act)]);
+                    let prompt = Box::new(prompt);
+
+                    return Err(UIError::NeedConfirm(prompt));
+                }
+
+                let _ = room.send_state_event(acl).await.map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
+            RoomAction::SpaceChildAdd(child) => {
+                if !self.room().is_space() {
+                    return Err(IambError::NotSpace.into());
+                }
+
+                let client = &store.application.worker.client;
+                let child = resolve_room(client, &child).await?;
+                let content = SpaceChildEventContent::new(via_servers(client, &child));
+
+                self.room()
+                    .send_state_event_for_key(&child, content)
+                    .await
+                    .map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
+            RoomAction::SpaceChildRemove(child) => {
+                if !self.room().is_space() {
+                    return Err(IambError::NotSpace.into());
+                }
+
+                let client = &store.application.worker.client;
+                let child = resolve_room(client, &child).await?;
+
+                // A child event without any content removes the room from the space.
+                self.room()
+                    .send_state_event_raw("m.space.child", child.as_str(), serde_json::json!({}))
+                    .await
+                    .map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
+            RoomAction::SpaceParentSet(parent) => {
+                let client = &store.application.worker.client;
+                let parent = resolve_room(client, &parent).await?;
+
+                let mut content = SpaceParentEventContent::new(via_servers(client, &parent));
+                content.canonical = true;
+
+                self.room()
+                    .send_state_event_for_key(&parent, content)
+                    .await
+                    .map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
+            RoomAction::StateShow(ty, key) => {
+                let client = &store.application.worker.client;
+                let msg =
+                    get_state_source(client, self.id(), ty.as_deref(), key.as_deref()).await?;
+                let msg = InfoMessage::Pager(msg);
+                let act = Action::ShowInfoMessage(msg);
+
+                Ok(vec![(act, ctx)])
+            },
+            RoomAction::StateEdit(ty, key) => {
+                let room = store
+                    .application
+                    .get_joined_room(self.id())
+                    .ok_or(UIError::Application(IambError::NotJoined))?;
+
+                let client = &store.application.worker.client;
+                let content = get_state_content(client, self.id(), &ty, &key).await?;
+                let content = content.unwrap_or_else(|| "{}".into());
+
+                let Some(value) = edit_json(&content)? else {
+                    return Ok(vec![]);
+                };
+
+                let _ = room
+                    .send_state_event_raw(&ty, &key, value)
+                    .await
+                    .map_err(IambError::from)?;
+
+                Ok(vec![])
+            },
             RoomAction::SetDirect(is_direct) => {
                 let room = store
                     .application
@@ -359,6 +785,11 @@ impl RoomState {
                         let ev = RoomHistoryVisibilityEventContent::new(visibility);
                         let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
                     },
+                    RoomField::JoinRule => {
+                        let rule = join_rule(&store.application.worker.client, &value).await?;
+                        let ev = RoomJoinRulesEventContent::new(rule);
+                        let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
+                    },
                     RoomField::Name => {
                         let ev = RoomNameEventContent::new(value);
                         let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
@@ -373,6 +804,10 @@ impl RoomState {
                         let ev = RoomTopicEventContent::new(value);
                         let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
                     },
+                    RoomField::Nick => {
+                        let user_id = &store.application.settings.profile.user_id;
+                        set_nick(&room, user_id, Some(value)).await?;
+                    },
                     RoomField::NotificationMode => {
                         let mode = notification_mode(value)?;
                         let client = &store.application.worker.client;
@@ -461,8 +896,23 @@ impl RoomState {
                         ev.alt_aliases = alt_aliases.into_iter().collect();
                         let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
                     },
-                    RoomField::Aliases => {
-                        // This never happens, aliases is only used for showing
+                    RoomField::PowerLevel(user_id) => {
+                        let level = power_level(value)?;
+                        let _ = room
+                            .update_power_levels(vec![(&user_id, level)])
+                            .await
+                            .map_err(IambError::from)?;
+                    },
+                    RoomField::EventPowerLevel(ev_type) => {
+                        let level = power_level(value)?;
+                        let mut levels = power_levels(&room).await?;
+                        levels.events.insert(ev_type, level);
+
+                        let ev = RoomPowerLevelsEventContent::from(levels);
+                        let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
+                    },
+                    RoomField::Aliases | RoomField::PowerLevels | RoomField::ServerAcl => {
+                        // This never happens, these are only used for showing
                     },
                 }
 
@@ -491,6 +941,13 @@ impl RoomState {
                         let ev = RoomTopicEventContent::new("".into());
                         let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
                     },
+                    RoomField::Nick => {
+                        let client = &store.application.worker.client;
+                        let user_id = &store.application.settings.profile.user_id;
+                        let name =
+                            client.account().get_display_name().await.map_err(IambError::from)?;
+                        set_nick(&room, user_id, name).await?;
+                    },
                     RoomField::NotificationMode => {
                         let client = &store.application.worker.client;
                         let notifications = client.notification_settings().await;
@@ -556,9 +1013,42 @@ impl RoomState {
                             .await
                             .map_err(IambError::from)?;
                     },
+                    RoomField::PowerLevel(user_id) => {
+                        let mut levels = power_levels(&room).await?;
+
+                        if levels.users.remove(&user_id).is_none() {
+                            let msg = format!("{user_id} is already using the default power level");
+
+                            return Ok(vec![(Action::ShowInfoMessage(msg.into()), ctx)]);
+                        }
+
+                        let ev = RoomPowerLevelsEventContent::from(levels);
+                        let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
+                    },
+                    RoomField::EventPowerLevel(ev_type) => {
+                        let mut levels = power_levels(&room).await?;
+
+                        if levels.events.remove(&ev_type).is_none() {
+                            let msg = format!("{ev_type} is already using the default power level");
+
+                            return Ok(vec![(Action::ShowInfoMessage(msg.into()), ctx)]);
+                        }
+
+                        let ev = RoomPowerLevelsEventContent::from(levels);
+                        let _ = room.send_state_event(ev).await.map_err(IambError::from)?;
+                    },
                     RoomField::Aliases => {
                         // This will not happen, you cannot unset all aliases
                     },
+                    RoomField::PowerLevels => {
+                        // This will not happen, you cannot unset all power levels
+                    },
+                    RoomField::JoinRule => {
+                        // This will not happen, you cannot unset the join rule
+                    },
+                    RoomField::ServerAcl => {
+                        // This will not happen, you cannot unset the server ACL
+                    },
                 }
 
                 Ok(vec![])
@@ -574,12 +1064,52 @@ impl RoomState {
                         let visibility = room.history_visibility();
                         format!("Room history visibility: {visibility}")
                     },
+                    RoomField::JoinRule => {
+                        let rule = room.join_rule();
+                        let allow = match &rule {
+                            JoinRule::Restricted(r) | JoinRule::KnockRestricted(r) => {
+                                r.allow.as_slice()
+                            },
+                            _ => &[],
+                        };
+
+                        let spaces = allow
+                            .iter()
+                            .filter_map(|rule| {
+                                match rule {
+                                    AllowRule::RoomMembership(m) => Some(&m.room_id),
+                                    _ => None,
+                                }
+                            })
+                            .map(|space| {
+                                let title = store.application.get_room_title(space);
+                                format!("{title} ({space})")
+                            })
+                            .collect::<Vec<_>>();
+
+                        if spaces.is_empty() {
+                            format!("Room join rule: {}", rule.as_str())
+                        } else {
+                            let spaces = spaces.join(", ");
+                            format!("Room join rule: {} (members of {spaces})", rule.as_str())
+                        }
+                    },
                     RoomField::Name => {
                         match room.name() {
                             None => "Room has no name".into(),
                             Some(name) => format!("Room name: {name:?}"),
                         }
                     },
+                    RoomField::Nick => {
+                        let user_id = &store.application.settings.profile.user_id;
+                        let member =
+                            room.get_member_no_sync(user_id).await.map_err(IambError::from)?;
+
+                        match member.as_ref().and_then(|m| m.display_name()) {
+                            None => "You have no display name in this room".into(),
+                            Some(name) => format!("Room nickname: {name:?}"),
+                        }
+                    },
                     RoomField::Topic => {
                         match room.topic() {
                             None => "Room has no topic".into(),
@@ -620,6 +1150,70 @@ impl RoomState {
                             Some(can) => format!("Canonical alias: {can}"),
                         }
                     },
+                    RoomField::PowerLevels => {
+                        let levels = power_levels(&room).await?;
+
+                        let mut users = levels.users.iter().collect::<Vec<_>>();
+                        users.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+
+                        let mut lines = vec![
+                            format!("Default user power level: {}", levels.users_default),
+                            format!("Default event power level: {}", levels.events_default),
+                            format!("Default state event power level: {}", levels.state_default),
+                            format!("Ban: {}", levels.ban),
+                            format!("Kick: {}", levels.kick),
+                            format!("Invite: {}", levels.invite),
+                            format!("Redact: {}", levels.redact),
+                        ];
+
+                        if !users.is_empty() {
+                            lines.push("Users:".into());
+                            lines.extend(users.into_iter().map(|(u, l)| format!("  {u}: {l}")));
+                        }
+
+                        if !levels.events.is_empty() {
+                            lines.push("Events:".into());
+                            lines.extend(levels.events.iter().map(|(e, l)| format!("  {e}: {l}")));
+                        }
+
+                        lines.join("\n")
+                    },
+                    RoomField::PowerLevel(user_id) => {
+                        let levels = power_levels(&room).await?;
+                        let level = levels.for_user(&user_id);
+
+                        format!("Power level for {user_id}: {level}")
+                    },
+                    RoomField::EventPowerLevel(ev_type) => {
+                        let levels = power_levels(&room).await?;
+
+                        match levels.events.get(&ev_type) {
+                            Some(level) => format!("Power level for {ev_type}: {level}"),
+                            None => {
+                                format!(
+                                    "Power level for {ev_type} is unset; the default is {} for state events and {} for other events",
+                                    levels.state_default, levels.events_default
+                                )
+                            },
+                        }
+                    },
+                    RoomField::ServerAcl => {
+                        let acl = server_acl(&room).await?;
+                        let list = |globs: &[String]| {
+                            if globs.is_empty() {
+                                "none".to_string()
+                            } else {
+                                globs.join(", ")
+                            }
+                        };
+
+                        format!(
+                            "Allowed servers: {}\nDenied servers: {}\nIP literals allowed: {}",
+                            list(&acl.allow),
+                            list(&acl.deny),
+                            acl.allow_ip_literals
+                        )
+                    },
                     RoomField::Tag(_) => "Cannot currently show value for a tag".into(),
                     RoomField::Alias(_) => {
                         "Cannot show a single alias; use `:room aliases show` instead.".into()
@@ -639,6 +1233,10 @@ impl RoomState {
         let style = Style::default().add_modifier(StyleModifier::BOLD);
         let mut spans = vec![];
 
+        if self.room().encryption_settings().is_some() {
+            spans.push("🔒 ".into());
+        }
+
         if let RoomState::Chat(chat) = self {
             if chat.thread().is_some() {
                 spans.push("Thread in ".into());
@@ -647,6 +1245,24 @@ impl RoomState {
 
         spans.push(Span::styled(title, style));
 
+        match store.application.rooms.get(self.id()).and_then(|info| info.purge) {
+            Some(PurgeProgress { scanning: true, found, .. }) => {
+                spans.push(format!(" [purging: {found} found]").into());
+            },
+            Some(PurgeProgress { found, redacted, failed, .. }) => {
+                let done = redacted + failed;
+                spans.push(format!(" [purging: {done}/{found}]").into());
+            },
+            None => {},
+        }
+
+        match self.room().pinned_event_ids() {
+            Some(pinned) if !pinned.is_empty() => {
+                spans.push(format!(" [{} pinned]", pinned.len()).into());
+            },
+            _ => {},
+        }
+
         match self.room().topic() {
             Some(desc) if !desc.is_empty() => {
                 spans.push(" (".into());
@@ -666,6 +1282,24 @@ impl RoomState {
         }
     }
 
+    pub fn fold(&mut self, act: FoldAction) -> IambResult<()> {
+        match self {
+            RoomState::Chat(_) => Ok(()),
+            RoomState::Space(space) => space.fold(act),
+        }
+    }
+
+    pub fn goto_message(&mut self, key: MessageKey) -> IambResult<()> {
+        match self {
+            RoomState::Chat(chat) => {
+                chat.goto_message(key);
+
+                Ok(())
+            },
+            RoomState::Space(_) => Err(IambError::NoSelectedRoom.into()),
+        }
+    }
+
     pub fn room(&self) -> &MatrixRoom {
         match self {
             RoomState::Chat(chat) => chat.room(),
@@ -802,6 +1436,27 @@ impl WindowOps<IambInfo> for RoomState {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_power_level() {
+        let tests = vec![
+            ("admin", Int::from(100)),
+            ("Moderator", Int::from(50)),
+            ("mod", Int::from(50)),
+            ("user", Int::from(0)),
+            ("75", Int::from(75)),
+            ("-1", Int::from(-1)),
+        ];
+
+        for (input, expect) in tests {
+            let res = power_level(input).unwrap();
+            assert_eq!(expect, res);
+        }
+
+        assert!(power_level("invalid").is_err());
+        assert!(power_level("1.5").is_err());
+        assert!(power_level("").is_err());
+    }
+
     #[test]
     fn test_parse_room_notification_level() {
         let tests = vec![
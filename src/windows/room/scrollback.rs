@@ -154,6 +154,13 @@ impl ScrollbackState {
         self.cursor = MessageCursor::latest();
     }
 
+    /// Move the cursor to the given message, and record the jump.
+    pub fn goto_message(&mut self, key: MessageKey) {
+        self.push_jump();
+        self.cursor = key.into();
+        self.push_jump();
+    }
+
     /// Set the dimensions and placement within the terminal window for this list.
     pub fn set_term_info(&mut self, area: Rect) {
         self.viewctx.dimensions = (area.width as usize, area.height as usize);
@@ -1290,6 +1297,7 @@ impl<'a> StatefulWidget for Scrollback<'a> {
     fn render(self, area: Rect, buf: &mut Buffer, state: &mut Self::State) {
         let info = self.store.application.rooms.get_or_default(state.room_id.clone());
         let settings = &self.store.application.settings;
+        let area = info.render_tombstone(area, buf);
         let area = if state.cursor.timestamp.is_some() {
             render_jump_to_recent(area, buf, self.focused)
         } else {
@@ -1,39 +1,275 @@
 //! Window for Matrix spaces
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
 use std::ops::{Deref, DerefMut};
 use std::time::{Duration, Instant};
 
 use matrix_sdk::{
     room::Room as MatrixRoom,
-    ruma::{OwnedRoomId, RoomId},
+    ruma::{
+        api::client::space::SpaceHierarchyRoomsChunk,
+        events::space::child::HierarchySpaceChildEvent,
+        room::RoomType,
+        OwnedRoomId,
+        OwnedServerName,
+        RoomId,
+        UInt,
+    },
+    RoomState as MatrixRoomState,
 };
 
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Color, Style},
+    style::{Color, Modifier as StyleModifier, Style},
     text::{Line, Span, Text},
     widgets::StatefulWidget,
 };
 
+use modalkit::actions::{PromptAction, Promptable};
+use modalkit::errors::{EditResult, UIError};
+use modalkit::prelude::*;
 use modalkit_ratatui::{
-    list::{List, ListState},
+    list::{List, ListCursor, ListItem, ListState},
     TermOffset,
     TerminalCursor,
     WindowOps,
 };
 
-use crate::base::{IambBufferId, IambInfo, ProgramStore, RoomFocus};
+use crate::base::{
+    ChatStore,
+    FoldAction,
+    HomeserverAction,
+    IambAction,
+    IambBufferId,
+    IambInfo,
+    IambResult,
+    Need,
+    ProgramAction,
+    ProgramContext,
+    ProgramStore,
+    RoomFocus,
+};
 
-use crate::windows::{room_fields_cmp, RoomItem};
+use crate::windows::{append_tags, name_and_labels, room_prompt, selected_style, some_cmp};
 
 const SPACE_HIERARCHY_DEBOUNCE: Duration = Duration::from_secs(5);
 
+/// A room's position within a flattened space hierarchy.
+struct TreeEntry<'a> {
+    chunk: &'a SpaceHierarchyRoomsChunk,
+    via: Vec<OwnedServerName>,
+    depth: usize,
+    has_children: bool,
+}
+
+fn child_events(chunk: &SpaceHierarchyRoomsChunk) -> Vec<HierarchySpaceChildEvent> {
+    if chunk.room_type != Some(RoomType::Space) {
+        return vec![];
+    }
+
+    // Children that have been removed from the space fail to deserialize, since they lack "via".
+    chunk
+        .children_state
+        .iter()
+        .filter_map(|ev| ev.deserialize().ok())
+        .collect()
+}
+
+fn chunk_name(chunk: &SpaceHierarchyRoomsChunk) -> String {
+    match (&chunk.name, &chunk.canonical_alias) {
+        (Some(name), _) => name.clone(),
+        (None, Some(alias)) => alias.to_string(),
+        (None, None) => chunk.room_id.to_string(),
+    }
+}
+
+fn child_cmp(
+    (a, a_ev): &(&SpaceHierarchyRoomsChunk, HierarchySpaceChildEvent),
+    (b, b_ev): &(&SpaceHierarchyRoomsChunk, HierarchySpaceChildEvent),
+) -> Ordering {
+    // Spaces can specify an order for their children, and otherwise we sort by name.
+    some_cmp(a_ev.content.order.as_ref(), b_ev.content.order.as_ref(), |a, b| a.cmp(b))
+        .then_with(|| chunk_name(a).to_lowercase().cmp(&chunk_name(b).to_lowercase()))
+        .then_with(|| a.room_id.cmp(&b.room_id))
+}
+
+/// Flatten the rooms in a space hierarchy into the order they're shown in, skipping the rooms
+/// inside of folded spaces.
+fn flatten_hierarchy<'a>(
+    root: &RoomId,
+    rooms: &'a [SpaceHierarchyRoomsChunk],
+    folded: &HashSet<OwnedRoomId>,
+) -> Vec<TreeEntry<'a>> {
+    let chunks = rooms.iter().map(|c| (c.room_id.as_ref(), c)).collect::<HashMap<_, _>>();
+    let mut ancestors = vec![root.to_owned()];
+    let mut entries = vec![];
+
+    flatten_children(root, 0, &chunks, folded, &mut ancestors, &mut entries);
+
+    return entries;
+}
+
+fn flatten_children<'a>(
+    parent: &RoomId,
+    depth: usize,
+    chunks: &HashMap<&RoomId, &'a SpaceHierarchyRoomsChunk>,
+    folded: &HashSet<OwnedRoomId>,
+    ancestors: &mut Vec<OwnedRoomId>,
+    entries: &mut Vec<TreeEntry<'a>>,
+) {
+    let Some(chunk) = chunks.get(parent) else {
+        return;
+    };
+
+    let mut children = child_events(chunk)
+        .into_iter()
+        .filter_map(|ev| Some((*chunks.get(&*ev.state_key)?, ev)))
+        .collect::<Vec<_>>();
+    children.sort_by(child_cmp);
+
+    for (chunk, ev) in children {
+        if ancestors.contains(&chunk.room_id) {
+            // Don't loop forever when spaces contain each other.
+            continue;
+        }
+
+        let has_children = !child_events(chunk).is_empty();
+        let via = ev.content.via;
+        entries.push(TreeEntry { chunk, via, depth, has_children });
+
+        if has_children && !folded.contains(&chunk.room_id) {
+            ancestors.push(chunk.room_id.clone());
+            flatten_children(&chunk.room_id, depth + 1, chunks, folded, ancestors, entries);
+            ancestors.pop();
+        }
+    }
+}
+
+/// A room or space shown in the hierarchy of a [Space].
+#[derive(Clone)]
+pub struct SpaceTreeItem {
+    room_id: OwnedRoomId,
+    via: Vec<OwnedServerName>,
+    name: String,
+    topic: Option<String>,
+    members: UInt,
+    depth: usize,
+    has_children: bool,
+    folded: bool,
+    joined: bool,
+    unread: bool,
+}
+
+impl SpaceTreeItem {
+    fn new(entry: TreeEntry, folded: &HashSet<OwnedRoomId>, store: &mut ProgramStore) -> Self {
+        let ChatStore { rooms, settings, worker, .. } = &mut store.application;
+        let room_id = entry.chunk.room_id.clone();
+        let joined = worker
+            .client
+            .get_room(&room_id)
+            .is_some_and(|room| room.state() == MatrixRoomState::Joined);
+
+        let (name, unread) = if joined {
+            let info = rooms.get_or_default(room_id.clone());
+            (info.name.clone(), info.unreads(settings).is_unread())
+        } else {
+            (None, false)
+        };
+
+        SpaceTreeItem {
+            name: name.unwrap_or_else(|| chunk_name(entry.chunk)),
+            topic: entry.chunk.topic.clone(),
+            members: entry.chunk.num_joined_members,
+            via: entry.via,
+            depth: entry.depth,
+            has_children: entry.has_children,
+            folded: folded.contains(&room_id),
+            room_id,
+            joined,
+            unread,
+        }
+    }
+}
+
+impl Display for SpaceTreeItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl ListItem<IambInfo> for SpaceTreeItem {
+    fn show(
+        &self,
+        selected: bool,
+        _: &ViewportContext<ListCursor>,
+        _: &mut ProgramStore,
+    ) -> Text<'_> {
+        let style = selected_style(selected);
+        let marker = match (self.has_children, self.folded) {
+            (false, _) => "  ",
+            (true, true) => "▸ ",
+            (true, false) => "▾ ",
+        };
+        let indent = format!("{}{marker}", "  ".repeat(self.depth));
+        let mut spans = vec![Span::styled(indent, style)];
+
+        if self.joined {
+            let (name, labels) = name_and_labels(&self.name, self.unread, style);
+            spans.push(name);
+            append_tags(labels, &mut spans, style);
+        } else {
+            // Show a preview of rooms we haven't joined yet.
+            let name = Span::styled(self.name.as_str(), style.add_modifier(StyleModifier::ITALIC));
+            let members = format!(" [not joined, {} members]", self.members);
+            spans.push(name);
+            spans.push(Span::styled(members, style));
+
+            if let Some(topic) = &self.topic {
+                let topic = topic.lines().next().unwrap_or_default();
+                spans.push(Span::styled(": ", style));
+                spans.push(Span::styled(topic, style));
+            }
+        }
+
+        Text::from(Line::from(spans))
+    }
+
+    fn get_word(&self) -> Option<String> {
+        self.room_id.to_string().into()
+    }
+}
+
+impl Promptable<ProgramContext, ProgramStore, IambInfo> for SpaceTreeItem {
+    fn prompt(
+        &mut self,
+        act: &PromptAction,
+        ctx: &ProgramContext,
+        _: &mut ProgramStore,
+    ) -> EditResult<Vec<(ProgramAction, ProgramContext)>, IambInfo> {
+        match act {
+            PromptAction::Submit if !self.joined => {
+                let join = HomeserverAction::JoinRoom(self.room_id.clone(), self.via.clone());
+                let act = IambAction::from(join);
+
+                Ok(vec![(act.into(), ctx.clone())])
+            },
+            _ => room_prompt(&self.room_id, act, ctx),
+        }
+    }
+}
+
 /// State needed for rendering [Space].
 pub struct SpaceState {
     room_id: OwnedRoomId,
     room: MatrixRoom,
-    list: ListState<RoomItem, IambInfo>,
+    list: ListState<SpaceTreeItem, IambInfo>,
+    hierarchy: Vec<SpaceHierarchyRoomsChunk>,
+    folded: HashSet<OwnedRoomId>,
+    needs_rebuild: bool,
     last_fetch: Option<Instant>,
+    last_update: Option<Instant>,
 }
 
 impl SpaceState {
@@ -41,9 +277,22 @@ impl SpaceState {
         let room_id = room.room_id().to_owned();
         let content = IambBufferId::Room(room_id.clone(), None, RoomFocus::Scrollback);
         let list = ListState::new(content, vec![]);
+        let hierarchy = vec![];
+        let folded = HashSet::new();
+        let needs_rebuild = false;
         let last_fetch = None;
+        let last_update = None;
 
-        SpaceState { room_id, room, list, last_fetch }
+        SpaceState {
+            room_id,
+            room,
+            list,
+            hierarchy,
+            folded,
+            needs_rebuild,
+            last_fetch,
+            last_update,
+        }
     }
 
     pub fn refresh_room(&mut self, store: &mut ProgramStore) {
@@ -60,12 +309,57 @@ impl SpaceState {
         &self.room_id
     }
 
+    pub fn fold(&mut self, act: FoldAction) -> IambResult<()> {
+        match act {
+            FoldAction::OpenAll => {
+                self.folded.clear();
+            },
+            FoldAction::CloseAll => {
+                self.folded = self
+                    .hierarchy
+                    .iter()
+                    .filter(|chunk| !child_events(chunk).is_empty())
+                    .map(|chunk| chunk.room_id.clone())
+                    .collect();
+            },
+            FoldAction::Open | FoldAction::Close | FoldAction::Toggle => {
+                let Some(item) = self.list.get().filter(|item| item.has_children) else {
+                    let msg = "Selected room is not a space containing other rooms";
+                    let err = UIError::Failure(msg.into());
+
+                    return Err(err);
+                };
+
+                let room_id = item.room_id.clone();
+                let fold = match act {
+                    FoldAction::Open => false,
+                    FoldAction::Close => true,
+                    _ => !self.folded.contains(&room_id),
+                };
+
+                if fold {
+                    self.folded.insert(room_id);
+                } else {
+                    self.folded.remove(&room_id);
+                }
+            },
+        }
+
+        self.needs_rebuild = true;
+
+        Ok(())
+    }
+
     pub fn dup(&self, store: &mut ProgramStore) -> Self {
         SpaceState {
             room_id: self.room_id.clone(),
             room: self.room.clone(),
             list: self.list.dup(store),
+            hierarchy: self.hierarchy.clone(),
+            folded: self.folded.clone(),
+            needs_rebuild: self.needs_rebuild,
             last_fetch: self.last_fetch,
+            last_update: self.last_update,
         }
     }
 }
@@ -77,7 +371,7 @@ impl TerminalCursor for SpaceState {
 }
 
 impl Deref for SpaceState {
-    type Target = ListState<RoomItem, IambInfo>;
+    type Target = ListState<SpaceTreeItem, IambInfo>;
 
     fn deref(&self) -> &Self::Target {
         &self.list
@@ -118,41 +412,42 @@ impl<'a> StatefulWidget for Space<'a> {
         };
 
         if need_fetch {
-            let res = self.store.application.worker.space_members(state.room_id.clone());
-
-            match res {
-                Ok(members) => {
-                    let mut items = members
-                        .into_iter()
-                        .filter_map(|id| {
-                            let (room, _, tags) =
-                                self.store.application.worker.get_room(id.clone()).ok()?;
-                            let room_info = std::sync::Arc::new((room, tags));
-
-                            if id != state.room_id {
-                                Some(RoomItem::new(room_info, self.store))
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-                    let fields = &self.store.application.settings.tunables.sort.rooms;
-                    items.sort_by(|a, b| room_fields_cmp(a, b, fields));
-
-                    state.list.set(items);
-                    state.last_fetch = Some(Instant::now());
-                },
-                Err(e) => {
-                    let lines = vec![
-                        Line::from("Unable to fetch space room hierarchy:"),
-                        Span::styled(e.to_string(), Style::default().fg(Color::Red)).into(),
-                    ];
-
-                    empty_message = Text::from(lines).into();
-                },
+            // The hierarchy gets fetched in the background, and stored in the room's info.
+            let room_id = state.room_id.clone();
+            self.store.application.need_load.insert(room_id, Need::HIERARCHY);
+            state.last_fetch = Some(Instant::now());
+        }
+
+        let info = self.store.application.rooms.get_or_default(state.room_id.clone());
+
+        if state.last_update != info.hierarchy_last {
+            state.last_update = info.hierarchy_last;
+
+            if let Some(Ok(hierarchy)) = &info.hierarchy {
+                state.hierarchy = hierarchy.clone();
+                state.needs_rebuild = true;
             }
         }
 
+        if let Some(Err(e)) = &info.hierarchy {
+            let lines = vec![
+                Line::from("Unable to fetch space room hierarchy:"),
+                Span::styled(e.clone(), Style::default().fg(Color::Red)).into(),
+            ];
+
+            empty_message = Text::from(lines).into();
+        }
+
+        if state.needs_rebuild {
+            let items = flatten_hierarchy(&state.room_id, &state.hierarchy, &state.folded)
+                .into_iter()
+                .map(|entry| SpaceTreeItem::new(entry, &state.folded, self.store))
+                .collect::<Vec<_>>();
+
+            state.list.set(items);
+            state.needs_rebuild = false;
+        }
+
         let mut list = List::new(self.store).focus(self.focused);
 
         if let Some(text) = empty_message {
@@ -162,3 +457,98 @@ impl<'a> StatefulWidget for Space<'a> {
         list.render(area, buffer, &mut state.list)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matrix_sdk::ruma::{
+        api::client::space::SpaceHierarchyRoomsChunkInit,
+        room_id,
+        serde::Raw,
+        space::SpaceRoomJoinRule,
+    };
+    use serde_json::json;
+
+    fn chunk(room_id: &RoomId, name: &str, children: &[&RoomId]) -> SpaceHierarchyRoomsChunk {
+        let children_state = children
+            .iter()
+            .map(|child| {
+                let ev = json!({
+                    "type": "m.space.child",
+                    "state_key": child,
+                    "sender": "@user:example.com",
+                    "origin_server_ts": 1,
+                    "content": { "via": ["example.com"] },
+                });
+
+                Raw::new(&ev).unwrap().cast()
+            })
+            .collect();
+
+        let mut chunk = SpaceHierarchyRoomsChunk::from(SpaceHierarchyRoomsChunkInit {
+            num_joined_members: 1u32.into(),
+            room_id: room_id.to_owned(),
+            world_readable: false,
+            guest_can_join: false,
+            join_rule: SpaceRoomJoinRule::Public,
+            children_state,
+        });
+        chunk.name = Some(name.into());
+
+        if !children.is_empty() {
+            chunk.room_type = Some(RoomType::Space);
+        }
+
+        chunk
+    }
+
+    fn flatten(
+        root: &RoomId,
+        rooms: &[SpaceHierarchyRoomsChunk],
+        folded: &HashSet<OwnedRoomId>,
+    ) -> Vec<(String, usize)> {
+        flatten_hierarchy(root, rooms, folded)
+            .into_iter()
+            .map(|entry| (chunk_name(entry.chunk), entry.depth))
+            .collect()
+    }
+
+    #[test]
+    fn test_flatten_hierarchy() {
+        let root = room_id!("!root:example.com");
+        let sub = room_id!("!sub:example.com");
+        let subsub = room_id!("!subsub:example.com");
+        let a = room_id!("!a:example.com");
+        let b = room_id!("!b:example.com");
+
+        let rooms = vec![
+            chunk(root, "Root", &[sub, a]),
+            chunk(sub, "Sub", &[subsub, b, root]),
+            chunk(subsub, "Subsub", &[a]),
+            chunk(a, "Alpha", &[]),
+            chunk(b, "Beta", &[]),
+        ];
+
+        // Children are sorted by name, and the loop back to the root is skipped.
+        let mut folded = HashSet::new();
+        assert_eq!(flatten(root, &rooms, &folded), vec![
+            ("Alpha".into(), 0),
+            ("Sub".into(), 0),
+            ("Beta".into(), 1),
+            ("Subsub".into(), 1),
+            ("Alpha".into(), 2),
+        ]);
+
+        // Folded spaces hide everything inside of them.
+        folded.insert(subsub.to_owned());
+        assert_eq!(flatten(root, &rooms, &folded), vec![
+            ("Alpha".into(), 0),
+            ("Sub".into(), 0),
+            ("Beta".into(), 1),
+            ("Subsub".into(), 1),
+        ]);
+
+        folded.insert(sub.to_owned());
+        assert_eq!(flatten(root, &rooms, &folded), vec![("Alpha".into(), 0), ("Sub".into(), 0)]);
+    }
+}
@@ -0,0 +1,104 @@
+//! Window for viewing the JSON source of an event
+use std::ops::{Deref, DerefMut};
+
+use matrix_sdk::ruma::{OwnedEventId, OwnedRoomId};
+use ratatui::{buffer::Buffer, layout::Rect};
+
+use modalkit_ratatui::{textbox::TextBoxState, TermOffset, TerminalCursor, WindowOps};
+
+use modalkit::editing::completion::CompletionList;
+use modalkit::prelude::*;
+
+use crate::base::{IambBufferId, IambInfo, IambResult, ProgramStore};
+
+pub struct SourceState {
+    room_id: OwnedRoomId,
+    event_id: OwnedEventId,
+    tbox: TextBoxState<IambInfo>,
+}
+
+impl SourceState {
+    pub fn new(room_id: OwnedRoomId, event_id: OwnedEventId, store: &mut ProgramStore) -> Self {
+        let id = IambBufferId::Source(room_id.clone(), event_id.clone());
+        let buf = store.buffers.load_str(id, "");
+
+        // Restored windows start out empty, since only :source fills in the buffer beforehand.
+        if buf.read().map(|b| b.is_blank()).unwrap_or(false) {
+            store.application.worker.fetch_source(room_id.clone(), event_id.clone());
+        }
+
+        let mut tbox = TextBoxState::new(buf);
+        tbox.set_readonly(true);
+
+        SourceState { room_id, event_id, tbox }
+    }
+
+    pub fn room_id(&self) -> &OwnedRoomId {
+        &self.room_id
+    }
+
+    pub fn event_id(&self) -> &OwnedEventId {
+        &self.event_id
+    }
+}
+
+impl Deref for SourceState {
+    type Target = TextBoxState<IambInfo>;
+
+    fn deref(&self) -> &Self::Target {
+        return &self.tbox;
+    }
+}
+
+impl DerefMut for SourceState {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        return &mut self.tbox;
+    }
+}
+
+impl TerminalCursor for SourceState {
+    fn get_term_cursor(&self) -> Option<TermOffset> {
+        self.tbox.get_term_cursor()
+    }
+}
+
+impl WindowOps<IambInfo> for SourceState {
+    fn draw(&mut self, area: Rect, buf: &mut Buffer, focused: bool, store: &mut ProgramStore) {
+        self.tbox.draw(area, buf, focused, store)
+    }
+
+    fn dup(&self, store: &mut ProgramStore) -> Self {
+        let tbox = self.tbox.dup(store);
+
+        SourceState {
+            room_id: self.room_id.clone(),
+            event_id: self.event_id.clone(),
+            tbox,
+        }
+    }
+
+    fn close(&mut self, flags: CloseFlags, store: &mut ProgramStore) -> bool {
+        self.tbox.close(flags, store)
+    }
+
+    fn write(
+        &mut self,
+        path: Option<&str>,
+        flags: WriteFlags,
+        store: &mut ProgramStore,
+    ) -> IambResult<EditInfo> {
+        self.tbox.write(path, flags, store)
+    }
+
+    fn get_completions(&self) -> Option<CompletionList> {
+        self.tbox.get_completions()
+    }
+
+    fn get_cursor_word(&self, style: &WordStyle) -> Option<String> {
+        self.tbox.get_cursor_word(style)
+    }
+
+    fn get_selected_word(&self) -> Option<String> {
+        self.tbox.get_selected_word()
+    }
+}
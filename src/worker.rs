@@ -2,18 +2,23 @@
 //!
 //! The worker thread handles asynchronous work, and can receive messages from the main thread that
 //! block on a reply from the async worker.
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::ffi::OsStr;
 use std::fmt::{Debug, Formatter};
 use std::ops::{Deref, DerefMut};
+use std::path::Path;
 use std::str::FromStr;
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+use edit::{edit_with_builder as external_edit, Builder};
 use futures::{stream::FuturesUnordered, StreamExt};
 use gethostname::gethostname;
 use matrix_sdk::ruma::events::AnySyncTimelineEvent;
+use mime::Mime;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 use tokio::sync::Semaphore;
 use tokio::task::JoinHandle;
@@ -22,22 +27,46 @@ use url::Url;
 
 use matrix_sdk::{
     config::{RequestConfig, SyncSettings},
-    deserialized_responses::DisplayName,
+    crypto::IdentityState,
+    deserialized_responses::{DisplayName, EncryptionInfo, TimelineEventKind},
     encryption::verification::{SasVerification, Verification},
-    encryption::{BackupDownloadStrategy, EncryptionSettings},
+    encryption::{
+        backups::BackupState,
+        recovery::RecoveryState,
+        BackupDownloadStrategy,
+        EncryptionSettings,
+    },
     event_handler::Ctx,
     matrix_auth::MatrixSession,
     reqwest,
     room::{Messages, MessagesOptions, Room as MatrixRoom, RoomMember},
     ruma::{
         api::client::{
+            directory::get_public_rooms_filtered::v3::Request as PublicRoomsRequest,
+            error::ErrorKind as ClientApiErrorKind,
             filter::{FilterDefinition, LazyLoadOptions, RoomEventFilter, RoomFilter},
+            presence::set_presence::v3::Request as SetPresenceRequest,
             room::create_room::v3::{CreationContent, Request as CreateRoomRequest, RoomPreset},
+            room::get_room_event::v3::Request as RoomEventRequest,
             room::Visibility,
-            space::get_hierarchy::v1::Request as SpaceHierarchyRequest,
+            search::search_events::v3::{
+                Categories as SearchCategories,
+                Criteria as SearchCriteria,
+                OrderBy as SearchOrderBy,
+                Request as SearchRequest,
+            },
+            space::{
+                get_hierarchy::v1::Request as SpaceHierarchyRequest,
+                SpaceHierarchyRoomsChunk,
+            },
+            state::get_state_events::v3::Request as RoomStateRequest,
+            state::get_state_events_for_key::v3::Request as StateEventRequest,
         },
         assign,
+        directory::{Filter as DirectoryFilter, PublicRoomsChunk},
         events::{
+            forwarded_room_key::ToDeviceForwardedRoomKeyEvent,
+            ignored_user_list::IgnoredUserListEventContent,
             key::verification::{
                 done::{OriginalSyncKeyVerificationDoneEvent, ToDeviceKeyVerificationDoneEvent},
                 key::{OriginalSyncKeyVerificationKeyEvent, ToDeviceKeyVerificationKeyEvent},
@@ -50,44 +79,65 @@ use matrix_sdk::{
             receipt::ReceiptType,
             receipt::{ReceiptEventContent, ReceiptThread},
             room::{
+                encrypted::{OriginalRoomEncryptedEvent, OriginalSyncRoomEncryptedEvent},
                 encryption::RoomEncryptionEventContent,
                 member::OriginalSyncRoomMemberEvent,
-                message::{MessageType, RoomMessageEventContent},
+                message::{MessageType, RoomMessageEvent, RoomMessageEventContent},
                 name::RoomNameEventContent,
                 redaction::OriginalSyncRoomRedactionEvent,
+                tombstone::RoomTombstoneEventContent,
             },
+            room_key::ToDeviceRoomKeyEvent,
+            space::{child::SpaceChildEventContent, parent::SpaceParentEventContent},
+            sticker::StickerEventContent,
             tag::Tags,
             typing::SyncTypingEvent,
             AnyInitialStateEvent,
             AnyMessageLikeEvent,
+            AnySyncMessageLikeEvent,
+            AnyTimelineEvent,
             EmptyStateKey,
+            GlobalAccountDataEvent,
             InitialStateEvent,
+            MessageLikeEvent,
+            StateEventType,
             SyncEphemeralRoomEvent,
             SyncMessageLikeEvent,
             SyncStateEvent,
         },
+        presence::PresenceState,
         room::RoomType,
         serde::Raw,
         EventEncryptionAlgorithm,
         EventId,
+        MilliSecondsSinceUnixEpoch,
+        OwnedDeviceId,
         OwnedEventId,
+        OwnedRoomAliasId,
         OwnedRoomId,
         OwnedRoomOrAliasId,
+        OwnedServerName,
         OwnedUserId,
         RoomId,
         RoomVersionId,
+        ServerName,
+        UInt,
+        UserId,
     },
     Client,
     ClientBuildError,
     Error as MatrixError,
+    LoopCtrl,
     RoomDisplayName,
     RoomMemberships,
+    RoomState as MatrixRoomState,
 };
 
 use modalkit::errors::UIError;
 use modalkit::prelude::{EditInfo, InfoMessage};
 
 use crate::base::Need;
+use crate::message::{sticker_to_message, MessageEncryption, MessageEvent, MessageTimeStamp};
 use crate::notifications::register_notifications;
 use crate::{
     base::{
@@ -95,11 +145,16 @@ use crate::{
         ChatStore,
         CreateRoomFlags,
         CreateRoomType,
+        DeviceEntry,
+        IambBufferId,
         IambError,
         IambResult,
         ProgramStore,
+        PurgeLimit,
+        PurgeProgress,
         RoomFetchStatus,
         RoomInfo,
+        SearchResult,
         VerifyAction,
     },
     ApplicationSettings,
@@ -114,9 +169,16 @@ const DEFAULT_ENCRYPTION_SETTINGS: EncryptionSettings = EncryptionSettings {
 const IAMB_DEVICE_NAME: &str = "iamb";
 const IAMB_USER_AGENT: &str = "iamb";
 const MIN_MSG_LOAD: u32 = 50;
+const MAX_SEARCH_RESULTS: usize = 50;
+const DIRECTORY_PAGE_SIZE: u32 = 50;
+const PURGE_MAX_PAGES: usize = 20;
+const PURGE_INTERVAL: Duration = Duration::from_millis(500);
+const SPACE_HIERARCHY_MAX_DEPTH: u32 = 5;
+const SPACE_HIERARCHY_MAX_PAGES: usize = 10;
+const SPACE_HIERARCHY_PAGE_SIZE: u32 = 100;
 
-type MessageFetchResult =
-    IambResult<(Option<String>, Vec<(AnyMessageLikeEvent, Vec<OwnedUserId>)>)>;
+type FetchedMessage = (AnyMessageLikeEvent, Vec<OwnedUserId>, Option<MessageEncryption>);
+type MessageFetchResult = IambResult<(Option<String>, Vec<FetchedMessage>)>;
 
 fn initial_devname() -> String {
     format!("{} on {}", IAMB_DEVICE_NAME, gethostname().to_string_lossy())
@@ -126,11 +188,84 @@ async fn is_direct(room: &MatrixRoom) -> bool {
     room.deref().is_direct().await.unwrap_or_default()
 }
 
+/// Get the servers to route through when pointing another room at `room_id`.
+pub fn via_servers(client: &Client, room_id: &RoomId) -> Vec<OwnedServerName> {
+    let mut via = vec![];
+
+    if let Some(user_id) = client.user_id() {
+        via.push(user_id.server_name().to_owned());
+    }
+
+    if let Some(server) = room_id.server_name() {
+        if !via.iter().any(|s| s == server) {
+            via.push(server.to_owned());
+        }
+    }
+
+    return via;
+}
+
+/// Get a space that we can add new rooms to.
+async fn parent_space(client: &Client, parent: &RoomId) -> IambResult<MatrixRoom> {
+    let Some(space) = client.get_room(parent).filter(|r| r.state() == MatrixRoomState::Joined)
+    else {
+        let msg = format!("You need to join {parent} before you can add rooms to it");
+        let err = UIError::Failure(msg);
+
+        return Err(err);
+    };
+
+    if !space.is_space() {
+        let msg = format!("{parent} is not a space");
+        let err = UIError::Failure(msg);
+
+        return Err(err);
+    }
+
+    let can_add = match client.user_id() {
+        Some(user_id) => {
+            space
+                .can_user_send_state(user_id, StateEventType::SpaceChild)
+                .await
+                .map_err(IambError::from)?
+        },
+        None => false,
+    };
+
+    if !can_add {
+        let msg = format!("You don't have permission to add rooms to {parent}");
+        let err = UIError::Failure(msg);
+
+        return Err(err);
+    }
+
+    return Ok(space);
+}
+
+/// Find the identifier for a room given either its identifier or one of its aliases.
+pub async fn resolve_room(client: &Client, name: &str) -> IambResult<OwnedRoomId> {
+    if let Ok(room_id) = OwnedRoomId::try_from(name) {
+        return Ok(room_id);
+    }
+
+    let Ok(alias) = OwnedRoomAliasId::try_from(name) else {
+        let msg = format!("{name:?} is not a valid room identifier or alias");
+        let err = UIError::Failure(msg);
+
+        return Err(err);
+    };
+
+    let resp = client.resolve_room_alias(&alias).await.map_err(IambError::from)?;
+
+    return Ok(resp.room_id);
+}
+
 pub async fn create_room(
     client: &Client,
     room_alias_name: Option<String>,
     rt: CreateRoomType,
     flags: CreateRoomFlags,
+    parent: Option<OwnedRoomId>,
 ) -> IambResult<OwnedRoomId> {
     let mut creation_content = None;
     let mut initial_state = vec![];
@@ -138,6 +273,13 @@ pub async fn create_room(
     let mut preset = None;
     let mut invite = vec![];
 
+    // Check that the room can be added to its parent before creating it, so that we don't leave
+    // behind a room that isn't in the space.
+    let space = match &parent {
+        Some(parent) => Some(parent_space(client, parent).await?),
+        None => None,
+    };
+
     let visibility = if flags.contains(CreateRoomFlags::PUBLIC) {
         Visibility::Public
     } else {
@@ -171,6 +313,16 @@ pub async fn create_room(
         initial_state.push(encr_raw);
     }
 
+    // Place the room inside its parent space.
+    if let Some(parent) = &parent {
+        let mut content = SpaceParentEventContent::new(via_servers(client, parent));
+        content.canonical = true;
+
+        let state = InitialStateEvent { content, state_key: parent.clone() };
+        let state_raw = Raw::new(&state).map_err(IambError::from)?;
+        initial_state.push(state_raw.cast::<AnyInitialStateEvent>());
+    }
+
     let request = assign!(CreateRoomRequest::new(), {
         room_alias_name,
         creation_content,
@@ -183,6 +335,14 @@ pub async fn create_room(
 
     let resp = client.create_room(request).await.map_err(IambError::from)?;
 
+    if let Some(space) = space {
+        let content = SpaceChildEventContent::new(via_servers(client, resp.room_id()));
+        space
+            .send_state_event_for_key(resp.room_id(), content)
+            .await
+            .map_err(IambError::from)?;
+    }
+
     if is_direct {
         if let Some(room) = client.get_room(resp.room_id()) {
             room.set_is_direct(true).await.map_err(IambError::from)?;
@@ -197,6 +357,543 @@ pub async fn create_room(
     return Ok(resp.room_id().to_owned());
 }
 
+/// Search for messages using the homeserver's search API.
+///
+/// When `room_id` is [None], every joined room is searched.
+pub async fn search_messages(
+    client: &Client,
+    term: String,
+    room_id: Option<OwnedRoomId>,
+) -> IambResult<Vec<SearchResult>> {
+    let mut criteria = SearchCriteria::new(term);
+    criteria.order_by = Some(SearchOrderBy::Recent);
+    criteria.filter.rooms = room_id.map(|room_id| vec![room_id]);
+
+    let mut categories = SearchCategories::new();
+    categories.room_events = Some(criteria);
+
+    let mut results = vec![];
+    let mut next_batch = None;
+
+    loop {
+        let mut request = SearchRequest::new(categories.clone());
+        request.next_batch = next_batch;
+
+        let resp = client.send(request, None).await.map_err(IambError::from)?;
+        let events = resp.search_categories.room_events;
+
+        for res in events.results {
+            let Some(ev) = res.result else {
+                continue;
+            };
+
+            let Ok(AnyTimelineEvent::MessageLike(AnyMessageLikeEvent::RoomMessage(
+                MessageLikeEvent::Original(ev),
+            ))) = ev.deserialize()
+            else {
+                continue;
+            };
+
+            results.push(SearchResult {
+                room_id: ev.room_id,
+                event_id: ev.event_id,
+                sender: ev.sender,
+                timestamp: ev.origin_server_ts.into(),
+                body: ev.content.body().to_string(),
+            });
+        }
+
+        next_batch = events.next_batch;
+
+        if next_batch.is_none() || results.len() >= MAX_SEARCH_RESULTS {
+            break;
+        }
+    }
+
+    results.truncate(MAX_SEARCH_RESULTS);
+
+    return Ok(results);
+}
+
+/// Fetch a page of rooms from a public room directory.
+///
+/// When `server` is [None], our homeserver's directory is listed. Returns the rooms along with
+/// the token for fetching the following page, if there is one.
+pub async fn public_rooms(
+    client: &Client,
+    server: Option<&ServerName>,
+    filter: Option<String>,
+    since: Option<String>,
+) -> IambResult<(Vec<PublicRoomsChunk>, Option<String>)> {
+    let mut request = PublicRoomsRequest::new();
+    request.server = server.map(ServerName::to_owned);
+    request.limit = Some(DIRECTORY_PAGE_SIZE.into());
+    request.since = since;
+    request.filter = assign!(DirectoryFilter::new(), { generic_search_term: filter });
+
+    let resp = client.public_rooms_filtered(request).await.map_err(IambError::from)?;
+
+    return Ok((resp.chunk, resp.next_batch));
+}
+
+/// Fetch the user's devices from the homeserver, along with their verification state.
+pub async fn list_devices(client: &Client, user_id: &UserId) -> IambResult<Vec<DeviceEntry>> {
+    let devices = client.devices().await.map_err(IambError::from)?.devices;
+    let crypto = client
+        .encryption()
+        .get_user_devices(user_id)
+        .await
+        .map_err(IambError::from)?;
+    let current = client.device_id();
+
+    let mut devices = devices
+        .into_iter()
+        .map(|device| {
+            let verified = crypto.get(&device.device_id).is_some_and(|d| d.is_verified());
+            let current = current == Some(device.device_id.as_ref());
+
+            DeviceEntry { device, verified, current }
+        })
+        .collect::<Vec<_>>();
+
+    // Show the current device first, and then the most recently used ones.
+    devices.sort_by(|a, b| {
+        b.current
+            .cmp(&a.current)
+            .then_with(|| b.device.last_seen_ts.cmp(&a.device.last_seen_ts))
+    });
+
+    return Ok(devices);
+}
+
+/// Describe a user's cross-signing identity and devices.
+///
+/// The [bool] argument indicates whether the user's identity has changed since it was verified.
+pub async fn describe_identity(
+    client: &Client,
+    user_id: &UserId,
+    violation: bool,
+) -> IambResult<String> {
+    let encryption = client.encryption();
+    let identity = encryption.get_user_identity(user_id).await.map_err(IambError::from)?;
+    let devices = encryption.get_user_devices(user_id).await.map_err(IambError::from)?;
+
+    let mut lines = vec![format!("User: {user_id}")];
+
+    match identity {
+        Some(identity) => {
+            let status = if identity.is_verified() {
+                "verified"
+            } else if violation {
+                "CHANGED since you verified it (use :verify withdraw to acknowledge)"
+            } else {
+                "not verified"
+            };
+            let master = identity.master_key().get_first_key().map(|k| k.to_base64());
+
+            lines.push("Cross-signing: set up".into());
+            lines.push(format!("Master key: {}", master.as_deref().unwrap_or("unknown")));
+            lines.push(format!("Identity: {status}"));
+        },
+        None => {
+            lines.push("Cross-signing: not set up".into());
+        },
+    }
+
+    lines.push(String::new());
+    lines.push("Devices:".into());
+
+    for device in devices.devices().filter(|d| !d.is_deleted()) {
+        let state = if device.is_verified() {
+            "verified"
+        } else if device.is_cross_signed_by_owner() {
+            "signed by owner"
+        } else {
+            "unverified"
+        };
+        let name = device.display_name().unwrap_or("");
+        let key = device.ed25519_key().map(|k| k.to_base64()).unwrap_or_default();
+
+        lines.push(format!("  {} {name:?} ({state})", device.device_id()));
+        lines.push(format!("    {key}"));
+    }
+
+    return Ok(lines.join("\n"));
+}
+
+/// Find the devices of joined members of an encrypted room that haven't been verified.
+pub async fn unverified_devices(client: &Client, room: &MatrixRoom) -> IambResult<Vec<String>> {
+    if !room.is_encrypted().await.map_err(IambError::from)? {
+        return Ok(vec![]);
+    }
+
+    let encryption = client.encryption();
+    let members = room.members(RoomMemberships::JOIN).await.map_err(IambError::from)?;
+    let own_device = client.device_id();
+    let mut unverified = vec![];
+
+    for member in members {
+        let user_id = member.user_id();
+        let devices = encryption.get_user_devices(user_id).await.map_err(IambError::from)?;
+
+        for device in devices.devices() {
+            if device.is_verified() || device.is_deleted() {
+                continue;
+            }
+
+            if Some(device.device_id()) == own_device {
+                continue;
+            }
+
+            unverified.push(format!("{user_id} ({})", device.device_id()));
+        }
+    }
+
+    return Ok(unverified);
+}
+
+/// Describe the state of server-side key backup and recovery.
+pub async fn key_backup_status(client: &Client) -> IambResult<String> {
+    let encryption = client.encryption();
+    let backups = encryption.backups();
+    let on_server = backups.exists_on_server().await.map_err(IambError::from)?;
+    let secret_storage = encryption.secret_storage().is_enabled().await.map_err(IambError::from)?;
+
+    let recovery = match encryption.recovery().state() {
+        RecoveryState::Enabled => "enabled",
+        RecoveryState::Disabled => "disabled",
+        RecoveryState::Incomplete => "incomplete (use :keys backup restore)",
+        RecoveryState::Unknown => "unknown",
+    };
+
+    let state = match backups.state() {
+        BackupState::Enabled => "enabled",
+        BackupState::Unknown => "disabled",
+        BackupState::Creating => "creating",
+        BackupState::Enabling => "enabling",
+        BackupState::Resuming => "resuming",
+        BackupState::Downloading => "downloading",
+        BackupState::Disabling => "disabling",
+    };
+
+    let yesno = |b: bool| if b { "yes" } else { "no" };
+    let msg = format!(
+        "Recovery: {recovery}\n\
+         Secret storage on server: {}\n\
+         Key backup on this device: {state}\n\
+         Key backup on server: {}",
+        yesno(secret_storage),
+        yesno(on_server),
+    );
+
+    return Ok(msg);
+}
+
+/// Import secrets from secret storage using a recovery key or passphrase, and then start
+/// fetching the room keys for every joined encrypted room from the key backup.
+///
+/// The room keys get downloaded in the background, and a notice is shown once they're done.
+pub async fn restore_key_backup(
+    client: &Client,
+    store: &AsyncProgramStore,
+    key: &str,
+) -> IambResult<()> {
+    client
+        .encryption()
+        .recovery()
+        .recover(key)
+        .await
+        .map_err(IambError::from)?;
+
+    tokio::spawn(download_backup_keys(client.clone(), store.clone()));
+
+    return Ok(());
+}
+
+async fn download_backup_keys(client: Client, store: AsyncProgramStore) {
+    let backups = client.encryption().backups();
+    let mut downloaded = 0;
+    let mut failed = 0;
+
+    for room in client.joined_rooms() {
+        if !room.is_encrypted().await.unwrap_or(false) {
+            continue;
+        }
+
+        let room_id = room.room_id();
+
+        if let Err(e) = backups.download_room_keys_for_room(room_id).await {
+            warn!(?room_id, err = e.to_string(), "Failed to download room keys from key backup");
+            failed += 1;
+        } else {
+            downloaded += 1;
+        }
+    }
+
+    retry_decryption_all(&client, &store).await;
+
+    let mut msg = format!("Downloaded room keys for {downloaded} encrypted rooms from key backup");
+
+    if failed > 0 {
+        msg.push_str(&format!(" ({failed} failed)"));
+    }
+
+    store.lock().await.application.notices.push(msg.into());
+}
+
+fn encrypted_raw(
+    ev: &OriginalRoomEncryptedEvent,
+) -> IambResult<Raw<OriginalSyncRoomEncryptedEvent>> {
+    let json = serde_json::json!({
+        "type": "m.room.encrypted",
+        "event_id": ev.event_id,
+        "sender": ev.sender,
+        "origin_server_ts": ev.origin_server_ts,
+        "room_id": ev.room_id,
+        "content": ev.content,
+    });
+
+    let raw = Raw::new(&json).map_err(IambError::from)?.cast();
+
+    return Ok(raw);
+}
+
+/// Try again to decrypt loaded messages in a room that previously couldn't be decrypted, now
+/// that new room keys may have arrived.
+///
+/// If a Megolm session is given, then only messages encrypted with it are retried.
+pub async fn retry_decryption(
+    client: &Client,
+    store: &AsyncProgramStore,
+    room_id: &RoomId,
+    session_id: Option<&str>,
+) {
+    let Some(room) = client.get_room(room_id) else {
+        return;
+    };
+
+    let pending = {
+        let mut locked = store.lock().await;
+        let info = locked.application.get_room_info(room_id.to_owned());
+        info.get_undecrypted(session_id)
+    };
+
+    for ev in pending {
+        let event_id = ev.event_id.clone();
+        let decrypted = match encrypted_raw(&ev) {
+            Ok(raw) => room.decrypt_event(&raw).await.map_err(|e| IambError::from(e).into()),
+            Err(e) => Err(e),
+        };
+
+        let decrypted = match decrypted {
+            Ok(decrypted) => decrypted,
+            Err(e) => {
+                warn!(event_id = event_id.as_str(), err = e.to_string(), "Failed to decrypt");
+                continue;
+            },
+        };
+
+        let mut locked = store.lock().await;
+        let ChatStore { rooms, picker, settings, index, .. } = &mut locked.application;
+        let info = rooms.get_or_default(room_id.to_owned());
+
+        if let TimelineEventKind::UnableToDecrypt { utd_info, .. } = &decrypted.kind {
+            let encryption = MessageEncryption::Undecryptable(utd_info.clone());
+            info.set_encryption(&event_id, Some(encryption));
+            continue;
+        }
+
+        let encryption = MessageEncryption::new(true, decrypted.encryption_info());
+        let Ok(AnySyncTimelineEvent::MessageLike(msg)) = decrypted.raw().deserialize() else {
+            continue;
+        };
+
+        info.remove_undecrypted(&event_id);
+
+        match sticker_as_message(msg.into_full_event(room_id.to_owned())) {
+            AnyMessageLikeEvent::RoomMessage(msg) => {
+                info.insert_with_preview(
+                    index,
+                    store.clone(),
+                    *picker,
+                    msg,
+                    settings,
+                    client.media(),
+                );
+                info.set_encryption(&event_id, encryption);
+            },
+            AnyMessageLikeEvent::Reaction(ev) => {
+                info.insert_reaction(ev);
+            },
+            ev if is_poll_event(&ev) => {
+                info.insert_poll(ev);
+                info.set_encryption(&event_id, encryption);
+            },
+            _ => continue,
+        }
+    }
+}
+
+/// Retry decrypting the loaded messages in every joined room.
+pub async fn retry_decryption_all(client: &Client, store: &AsyncProgramStore) {
+    for room in client.joined_rooms() {
+        retry_decryption(client, store, room.room_id(), None).await;
+    }
+}
+
+/// Fetch a single message event from the homeserver.
+pub async fn get_message_event(
+    client: &Client,
+    room_id: &RoomId,
+    event_id: &EventId,
+) -> IambResult<AnyMessageLikeEvent> {
+    let Some(room) = client.get_room(room_id) else {
+        return Err(IambError::UnknownRoom(room_id.to_owned()).into());
+    };
+
+    let ev = room.event(event_id, None).await.map_err(IambError::from)?;
+    let ev = ev.into_raw().deserialize().map_err(IambError::Serde)?;
+
+    match ev {
+        AnySyncTimelineEvent::MessageLike(ev) => {
+            Ok(sticker_as_message(ev.into_full_event(room_id.to_owned())))
+        },
+        AnySyncTimelineEvent::State(_) => Err(IambError::UnknownEvent(event_id.to_owned()).into()),
+    }
+}
+
+/// Fetch the JSON source of an event, decrypting it when it's encrypted.
+///
+/// For encrypted events, both the decrypted event and the original encrypted envelope are
+/// included in the returned text.
+pub async fn get_event_source(
+    client: &Client,
+    room_id: &RoomId,
+    event_id: &EventId,
+) -> IambResult<String> {
+    let Some(room) = client.get_room(room_id) else {
+        return Err(IambError::UnknownRoom(room_id.to_owned()).into());
+    };
+
+    let req = RoomEventRequest::new(room_id.to_owned(), event_id.to_owned());
+    let raw = client.send(req, None).await.map_err(IambError::from)?.event;
+    let envelope = pretty_json(raw.json().get())?;
+    let ty = raw.get_field::<String>("type").map_err(IambError::from)?;
+
+    if ty.as_deref() != Some("m.room.encrypted") {
+        return Ok(envelope);
+    }
+
+    let ev = room.decrypt_event(raw.cast_ref()).await.map_err(IambError::from)?;
+    let decrypted = match &ev.kind {
+        TimelineEventKind::Decrypted(dec) => pretty_json(dec.event.json().get())?,
+        TimelineEventKind::UnableToDecrypt { utd_info, .. } => {
+            format!("Unable to decrypt event: {:?}", utd_info.reason)
+        },
+        TimelineEventKind::PlainText { event } => pretty_json(event.json().get())?,
+    };
+
+    return Ok(format!("Decrypted event:\n\n{decrypted}\n\nEncrypted event:\n\n{envelope}"));
+}
+
+fn pretty_json(json: &str) -> IambResult<String> {
+    let value = serde_json::from_str::<serde_json::Value>(json).map_err(IambError::from)?;
+    let pretty = serde_json::to_string_pretty(&value).map_err(IambError::from)?;
+
+    return Ok(pretty);
+}
+
+/// Open JSON in the external editor, and parse the edited result into an object.
+///
+/// This returns [None] if the JSON was left unchanged or emptied out.
+pub fn edit_json(json: &str) -> IambResult<Option<serde_json::Value>> {
+    let edited = external_edit(json, Builder::new().suffix(".json")).map_err(IambError::from)?;
+    let edited = edited.trim();
+
+    if edited.is_empty() || edited == json.trim() {
+        return Ok(None);
+    }
+
+    let value = serde_json::from_str::<serde_json::Value>(edited)
+        .map_err(|e| IambError::InvalidJson(e.to_string()))?;
+
+    if !value.is_object() {
+        let err = IambError::InvalidJson("the content must be a JSON object".into());
+
+        return Err(err.into());
+    }
+
+    return Ok(Some(value));
+}
+
+/// Fetch the content of one of the user's global account data events, if it exists.
+pub async fn global_account_data(client: &Client, event_type: &str) -> IambResult<Option<String>> {
+    let raw = client
+        .account()
+        .account_data_raw(event_type.into())
+        .await
+        .map_err(IambError::from)?;
+
+    match raw {
+        Some(raw) => Ok(Some(pretty_json(raw.json().get())?)),
+        None => Ok(None),
+    }
+}
+
+/// Fetch a room's state events, optionally filtered by event type and state key.
+pub async fn get_state_source(
+    client: &Client,
+    room_id: &RoomId,
+    event_type: Option<&str>,
+    state_key: Option<&str>,
+) -> IambResult<String> {
+    let req = RoomStateRequest::new(room_id.to_owned());
+    let state = client.send(req, None).await.map_err(IambError::from)?.room_state;
+    let mut events = vec![];
+
+    for ev in state {
+        let ty = ev.get_field::<String>("type").map_err(IambError::from)?;
+        let key = ev.get_field::<String>("state_key").map_err(IambError::from)?;
+
+        if event_type.is_some_and(|t| ty.as_deref() != Some(t)) {
+            continue;
+        }
+
+        if state_key.is_some_and(|k| key.as_deref() != Some(k)) {
+            continue;
+        }
+
+        events.push(pretty_json(ev.json().get())?);
+    }
+
+    if events.is_empty() {
+        let msg = "No matching state events found";
+        let err = UIError::Failure(msg.into());
+
+        return Err(err);
+    }
+
+    return Ok(events.join("\n\n"));
+}
+
+/// Fetch the content of a single state event, if it exists.
+pub async fn get_state_content(
+    client: &Client,
+    room_id: &RoomId,
+    event_type: &str,
+    state_key: &str,
+) -> IambResult<Option<String>> {
+    let req = StateEventRequest::new(room_id.to_owned(), event_type.into(), state_key.into());
+
+    match client.send(req, None).await {
+        Ok(resp) => Ok(Some(pretty_json(resp.content.json().get())?)),
+        Err(e) if matches!(e.client_api_error_kind(), Some(ClientApiErrorKind::NotFound)) => {
+            Ok(None)
+        },
+        Err(e) => Err(IambError::from(e).into()),
+    }
+}
+
 async fn update_event_receipts(info: &mut RoomInfo, room: &MatrixRoom, event_id: &EventId) {
     let receipts = match room
         .load_event_receipts(ReceiptType::Read, ReceiptThread::Main, event_id)
@@ -214,10 +911,38 @@ async fn update_event_receipts(info: &mut RoomInfo, room: &MatrixRoom, event_id:
     }
 }
 
+/// Insert a message received while syncing into its room.
+async fn insert_sync_message(
+    ev: RoomMessageEvent,
+    room: &MatrixRoom,
+    client: &Client,
+    encryption: Option<MessageEncryption>,
+    store: &AsyncProgramStore,
+) {
+    let mut locked = store.lock().await;
+
+    if locked.application.is_ignored(ev.sender()) {
+        return;
+    }
+
+    let sender = ev.sender().to_owned();
+    let _ = locked.application.presences.get_or_default(sender);
+
+    let ChatStore { rooms, picker, settings, index, .. } = &mut locked.application;
+    let info = rooms.get_or_default(room.room_id().to_owned());
+
+    update_event_receipts(info, room, ev.event_id()).await;
+
+    let event_id = ev.event_id().to_owned();
+    info.insert_with_preview(index, store.clone(), *picker, ev, settings, client.media());
+    info.set_encryption(&event_id, encryption);
+}
+
 #[derive(Debug)]
 enum Plan {
     Messages(OwnedRoomId, Option<String>),
     Members(OwnedRoomId),
+    Hierarchy(OwnedRoomId),
 }
 
 async fn load_plans(store: &AsyncProgramStore) -> Vec<Plan> {
@@ -247,6 +972,16 @@ async fn load_plans(store: &AsyncProgramStore) -> Vec<Plan> {
             plan.push(Plan::Members(room_id.to_owned()));
             need.remove(Need::MEMBERS);
         }
+        if need.contains(Need::HIERARCHY) {
+            let info = rooms.get_or_default(room_id.clone());
+
+            if !info.fetching_hierarchy {
+                info.fetching_hierarchy = true;
+                plan.push(Plan::Hierarchy(room_id.to_owned()));
+            }
+
+            need.remove(Need::HIERARCHY);
+        }
         if !need.is_empty() {
             need_load.insert(room_id, need);
         }
@@ -272,6 +1007,11 @@ async fn run_plan(client: &Client, store: &AsyncProgramStore, plan: Plan, permit
             let mut locked = store.lock().await;
             members_insert(room_id, res, locked.deref_mut());
         },
+        Plan::Hierarchy(room_id) => {
+            let res = hierarchy_load(client, &room_id).await;
+            let mut locked = store.lock().await;
+            hierarchy_insert(room_id, res, locked.deref_mut());
+        },
     }
     drop(permit);
 }
@@ -290,13 +1030,22 @@ async fn load_older_one(
         opts.limit = limit.into();
 
         let Messages { end, chunk, .. } = room.messages(opts).await.map_err(IambError::from)?;
+        let encrypted = room.is_encrypted().await.unwrap_or(false);
 
         let mut msgs = vec![];
 
         for ev in chunk.into_iter() {
+            let encryption = match &ev.kind {
+                TimelineEventKind::UnableToDecrypt { utd_info, .. } => {
+                    Some(MessageEncryption::Undecryptable(utd_info.clone()))
+                },
+                _ => MessageEncryption::new(encrypted, ev.encryption_info()),
+            };
             let deserialized = ev.into_raw().deserialize().map_err(IambError::Serde)?;
             let msg: AnyMessageLikeEvent = match deserialized {
-                AnySyncTimelineEvent::MessageLike(e) => e.into_full_event(room_id.to_owned()),
+                AnySyncTimelineEvent::MessageLike(e) => {
+                    sticker_as_message(e.into_full_event(room_id.to_owned()))
+                },
                 AnySyncTimelineEvent::State(_) => continue,
             };
 
@@ -312,7 +1061,7 @@ async fn load_older_one(
                 },
             };
 
-            msgs.push((msg, receipts));
+            msgs.push((msg, receipts, encryption));
         }
 
         Ok((end, msgs))
@@ -321,120 +1070,501 @@ async fn load_older_one(
     }
 }
 
+/// Treat stickers as image messages, so that they're inserted into the scrollback.
+fn sticker_as_message(ev: AnyMessageLikeEvent) -> AnyMessageLikeEvent {
+    match ev {
+        AnyMessageLikeEvent::Sticker(ev) => {
+            AnyMessageLikeEvent::RoomMessage(sticker_to_message(ev))
+        },
+        ev => ev,
+    }
+}
+
+/// Whether an event starts, votes in, or ends a poll.
+fn is_poll_event(ev: &AnyMessageLikeEvent) -> bool {
+    matches!(
+        ev,
+        AnyMessageLikeEvent::PollStart(_) |
+            AnyMessageLikeEvent::PollResponse(_) |
+            AnyMessageLikeEvent::PollEnd(_) |
+            AnyMessageLikeEvent::UnstablePollStart(_) |
+            AnyMessageLikeEvent::UnstablePollResponse(_) |
+            AnyMessageLikeEvent::UnstablePollEnd(_)
+    )
+}
+
 fn load_insert(
     room_id: OwnedRoomId,
     res: MessageFetchResult,
     locked: &mut ProgramStore,
     store: AsyncProgramStore,
 ) {
-    let ChatStore { presences, rooms, worker, picker, settings, .. } = &mut locked.application;
+    let ChatStore {
+        presences,
+        rooms,
+        worker,
+        picker,
+        settings,
+        index,
+        ignored,
+        ..
+    } = &mut locked.application;
     let info = rooms.get_or_default(room_id.clone());
     info.fetching = false;
     let client = &worker.client;
 
     match res {
         Ok((fetch_id, msgs)) => {
-            for (msg, receipts) in msgs.into_iter() {
-                let sender = msg.sender().to_owned();
-                let _ = presences.get_or_default(sender);
+            // Index the whole page in one transaction instead of committing each message.
+            index.batch(|| {
+                for (msg, receipts, encryption) in msgs.into_iter() {
+                    let sender = msg.sender().to_owned();
+
+                    if ignored.contains(&sender) {
+                        continue;
+                    }
+
+                    let _ = presences.get_or_default(sender);
+
+                    for user_id in receipts {
+                        info.set_receipt(user_id, msg.event_id().to_owned());
+                    }
+
+                    match msg {
+                        AnyMessageLikeEvent::RoomEncrypted(msg) => {
+                            let event_id = msg.event_id().to_owned();
+
+                            info.insert_encrypted(msg);
+                            info.set_encryption(&event_id, encryption);
+                        },
+                        AnyMessageLikeEvent::RoomMessage(msg) => {
+                            let event_id = msg.event_id().to_owned();
+
+                            info.insert_with_preview(
+                                index,
+                                store.clone(),
+                                *picker,
+                                msg,
+                                settings,
+                                client.media(),
+                            );
+                            info.set_encryption(&event_id, encryption);
+                        },
+                        AnyMessageLikeEvent::Reaction(ev) => {
+                            info.insert_reaction(ev);
+                        },
+                        ev if is_poll_event(&ev) => {
+                            let event_id = ev.event_id().to_owned();
+
+                            info.insert_poll(ev);
+                            info.set_encryption(&event_id, encryption);
+                        },
+                        _ => continue,
+                    }
+                }
+            });
+
+            info.fetch_id = fetch_id.map_or(RoomFetchStatus::Done, RoomFetchStatus::HaveMore);
+        },
+        Err(e) => {
+            warn!(room_id = room_id.as_str(), err = e.to_string(), "Failed to load older messages");
+
+            // Wait and try again.
+            locked.application.need_load.insert(room_id, Need::MESSAGES);
+        },
+    }
+}
+
+async fn load_older(client: &Client, store: &AsyncProgramStore) -> usize {
+    // This is an arbitrary limit on how much work we do in parallel to avoid
+    // spawning too many tasks at startup and overwhelming the client. We
+    // should normally only surpass this limit at startup when doing an initial.
+    // fetch for each room.
+    const LIMIT: usize = 15;
+
+    // Plans are run in parallel. Any room *may* have several plans.
+    let plans = load_plans(store).await;
+    let permits = Semaphore::new(LIMIT);
+
+    plans
+        .into_iter()
+        .map(|plan| run_plan(client, store, plan, &permits))
+        .collect::<FuturesUnordered<_>>()
+        .count()
+        .await
+}
+
+async fn members_load(client: &Client, room_id: &RoomId) -> IambResult<Vec<RoomMember>> {
+    if let Some(room) = client.get_room(room_id) {
+        Ok(room
+            .members_no_sync(RoomMemberships::all())
+            .await
+            .map_err(IambError::from)?)
+    } else {
+        Err(IambError::UnknownRoom(room_id.to_owned()).into())
+    }
+}
+
+fn members_insert(
+    room_id: OwnedRoomId,
+    res: IambResult<Vec<RoomMember>>,
+    store: &mut ProgramStore,
+) {
+    if let Ok(members) = res {
+        let ChatStore { rooms, .. } = &mut store.application;
+        let info = rooms.get_or_default(room_id);
+
+        for member in members {
+            let user_id = member.user_id();
+            let display_name =
+                member.display_name().map_or(user_id.to_string(), |str| str.to_string());
+            info.display_names.insert(user_id.to_owned(), display_name);
+        }
+    }
+    // else ???
+}
+
+async fn hierarchy_load(
+    client: &Client,
+    space: &RoomId,
+) -> IambResult<Vec<SpaceHierarchyRoomsChunk>> {
+    let mut rooms = vec![];
+    let mut from = None;
+
+    // Spaces can be very large, so only fetch a bounded number of pages.
+    for _ in 0..SPACE_HIERARCHY_MAX_PAGES {
+        let mut req = SpaceHierarchyRequest::new(space.to_owned());
+        req.limit = Some(SPACE_HIERARCHY_PAGE_SIZE.into());
+        req.max_depth = Some(SPACE_HIERARCHY_MAX_DEPTH.into());
+        req.from = from;
+
+        let resp = client.send(req, None).await.map_err(IambError::from)?;
+        rooms.extend(resp.rooms);
+
+        if resp.next_batch.is_none() {
+            break;
+        }
+
+        from = resp.next_batch;
+    }
+
+    Ok(rooms)
+}
+
+fn hierarchy_insert(
+    room_id: OwnedRoomId,
+    res: IambResult<Vec<SpaceHierarchyRoomsChunk>>,
+    store: &mut ProgramStore,
+) {
+    let info = store.application.rooms.get_or_default(room_id);
+    info.hierarchy = Some(res.map_err(|e| e.to_string()));
+    info.hierarchy_last = Some(Instant::now());
+    info.fetching_hierarchy = false;
+}
+
+async fn load_older_forever(client: &Client, store: &AsyncProgramStore) {
+    // Load any pending older messages or members every 2 seconds.
+    let mut interval = tokio::time::interval(Duration::from_secs(2));
+
+    loop {
+        interval.tick().await;
+        load_older(client, store).await;
+    }
+}
+
+/// Set our presence and status message.
+pub async fn set_presence(
+    client: &Client,
+    user_id: &UserId,
+    presence: PresenceState,
+    status_msg: Option<String>,
+) -> IambResult<()> {
+    let req = assign!(SetPresenceRequest::new(user_id.to_owned(), presence), { status_msg });
+    let _ = client.send(req, None).await.map_err(IambError::from)?;
+
+    Ok(())
+}
+
+/// Mark ourselves as unavailable once iamb has been in the background for a while, and then as
+/// online again when it's focused.
+async fn auto_away_forever(
+    client: &Client,
+    settings: &ApplicationSettings,
+    store: &AsyncProgramStore,
+) {
+    let Some(away_after) = settings.tunables.away_after.map(Duration::from_secs) else {
+        return;
+    };
+
+    let user_id = &settings.profile.user_id;
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    let mut unfocused: Option<Instant> = None;
+    let mut away = false;
+
+    loop {
+        interval.tick().await;
+
+        let mut locked = store.lock().await;
+        let ChatStore { focused, presence, presences, status_msg, .. } = &mut locked.application;
+
+        let update = if *focused {
+            unfocused = None;
+
+            // Only come back if nobody has changed the presence since we went away.
+            let back = away && *presence == PresenceState::Unavailable;
+            away = false;
+
+            back.then_some(PresenceState::Online)
+        } else {
+            let since = *unfocused.get_or_insert_with(Instant::now);
+            let idle = !away && *presence == PresenceState::Online;
+
+            if idle && since.elapsed() >= away_after {
+                away = true;
+                Some(PresenceState::Unavailable)
+            } else {
+                None
+            }
+        };
+
+        if let Some(update) = update {
+            *presence = update.clone();
+            presences.insert(user_id.clone(), update.clone());
+            let status_msg = status_msg.clone();
+            drop(locked);
+
+            if let Err(e) = set_presence(client, user_id, update, status_msg).await {
+                warn!(err = e.to_string(), "Failed to update presence");
+            }
+        }
+    }
+}
+
+/// Read a file to upload, along with the name and content type to upload it as.
+pub fn read_upload(path: &Path) -> IambResult<(String, Mime, Vec<u8>)> {
+    let mime = mime_guess::from_path(path).first_or(mime::APPLICATION_OCTET_STREAM);
+    let bytes = std::fs::read(path).map_err(IambError::from)?;
+    let name = path
+        .file_name()
+        .map(OsStr::to_string_lossy)
+        .unwrap_or_else(|| Cow::from("Attachment"))
+        .into_owned();
+
+    Ok((name, mime, bytes))
+}
+
+/// Fetch the users we're ignoring from our account data.
+pub async fn ignored_users(client: &Client) -> IambResult<HashSet<OwnedUserId>> {
+    let ignored = client
+        .account()
+        .account_data::<IgnoredUserListEventContent>()
+        .await
+        .map_err(IambError::from)?
+        .map(|raw| raw.deserialize())
+        .transpose()
+        .map_err(IambError::Serde)?
+        .map(|content| content.ignored_users.into_keys().collect())
+        .unwrap_or_default();
+
+    Ok(ignored)
+}
+
+/// Get a token for fetching the messages from before an event.
+async fn prev_batch(client: &Client, room_id: &RoomId, event_id: &EventId) -> Option<String> {
+    let room = client.get_room(room_id)?;
+    let resp = room.event_with_context(event_id, false, UInt::MIN, None).await.ok()?;
+
+    resp.prev_batch_token
+}
+
+/// Find the events to redact for a purge, starting with the messages we've already loaded, and
+/// then paging further back through the room's history.
+async fn purge_events(
+    client: &Client,
+    store: &AsyncProgramStore,
+    room_id: &RoomId,
+    user_id: &UserId,
+    limit: &PurgeLimit,
+) -> IambResult<Vec<OwnedEventId>> {
+    let (max, cutoff) = match limit {
+        PurgeLimit::Count(n) => (*n, None),
+        PurgeLimit::Since(d) => {
+            let now = MilliSecondsSinceUnixEpoch::now().get();
+            let ago = UInt::try_from(d.as_millis()).unwrap_or(UInt::MAX);
+            (usize::MAX, Some(now.saturating_sub(ago)))
+        },
+    };
+
+    let mut events = vec![];
+    let mut seen = HashSet::new();
+
+    let (mut fetch_id, oldest) = {
+        let mut locked = store.lock().await;
+        let info = locked.application.rooms.get_or_default(room_id.to_owned());
+
+        // Fetching older messages returns thread replies too, so check the loaded ones as well.
+        let mut loaded = info
+            .all_messages()
+            .filter_map(|((ts, event_id), msg)| {
+                let MessageTimeStamp::OriginServer(ts) = ts else {
+                    return None;
+                };
+
+                Some((*ts, event_id, msg))
+            })
+            .collect::<Vec<_>>();
+        loaded.sort_by(|(a, _, _), (b, _, _)| b.cmp(a));
+
+        let oldest = loaded.last().map(|(_, event_id, _)| (*event_id).clone());
+
+        for (ts, event_id, msg) in loaded {
+            if cutoff.is_some_and(|cutoff| ts < cutoff) || events.len() >= max {
+                return Ok(events);
+            }
+
+            seen.insert(event_id.clone());
+
+            let redactable =
+                matches!(msg.event, MessageEvent::Original(_) | MessageEvent::EncryptedOriginal(_));
+
+            if redactable && msg.sender == user_id {
+                events.push(event_id.clone());
+            }
+        }
+
+        if let Some(progress) = &mut info.purge {
+            progress.found = events.len();
+        }
+
+        match &info.fetch_id {
+            RoomFetchStatus::Done => return Ok(events),
+            RoomFetchStatus::HaveMore(fetch_id) => (Some(fetch_id.clone()), None),
+            RoomFetchStatus::NotStarted => (None, oldest),
+        }
+    };
+
+    if let Some(oldest) = oldest {
+        // Messages received while syncing may have been loaded before any older ones were
+        // fetched, so continue from the oldest of them instead of from the newest message.
+        fetch_id = prev_batch(client, room_id, &oldest).await;
+    }
+
+    for _ in 0..PURGE_MAX_PAGES {
+        let (end, msgs) = load_older_one(client, room_id, fetch_id, MIN_MSG_LOAD).await?;
+
+        for (msg, _, _) in msgs {
+            if !seen.insert(msg.event_id().to_owned()) {
+                continue;
+            }
+
+            if cutoff.is_some_and(|cutoff| msg.origin_server_ts().get() < cutoff) ||
+                events.len() >= max
+            {
+                return Ok(events);
+            }
+
+            let redactable = matches!(
+                msg,
+                AnyMessageLikeEvent::RoomMessage(_) | AnyMessageLikeEvent::RoomEncrypted(_)
+            ) && msg.original_content().is_some();
+
+            if redactable && msg.sender() == user_id {
+                events.push(msg.event_id().to_owned());
+            }
+        }
+
+        if let Some(info) = store.lock().await.application.rooms.get_mut(room_id) {
+            if let Some(progress) = &mut info.purge {
+                progress.found = events.len();
+            }
+        }
+
+        match end {
+            Some(end) => fetch_id = Some(end),
+            None => break,
+        }
+    }
+
+    Ok(events)
+}
+
+/// Redact a user's recent messages in a room, showing progress in the room's title and a
+/// summary once finished.
+async fn purge(
+    client: Client,
+    store: AsyncProgramStore,
+    room_id: OwnedRoomId,
+    user_id: OwnedUserId,
+    limit: PurgeLimit,
+    reason: Option<String>,
+) {
+    let res = match client.get_room(&room_id) {
+        Some(room) => {
+            purge_events(&client, &store, &room_id, &user_id, &limit)
+                .await
+                .map(|events| (room, events))
+        },
+        None => Err(IambError::UnknownRoom(room_id.clone()).into()),
+    };
+    let mut progress = PurgeProgress::default();
+
+    let scan_error = match res {
+        Ok((room, events)) => {
+            progress.found = events.len();
+
+            let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+            for event_id in events {
+                interval.tick().await;
 
-                for user_id in receipts {
-                    info.set_receipt(user_id, msg.event_id().to_owned());
+                if let Err(e) = room.redact(&event_id, reason.as_deref(), None).await {
+                    warn!(?event_id, err = e.to_string(), "Failed to redact event during purge");
+                    progress.failed += 1;
+                } else {
+                    progress.redacted += 1;
                 }
 
-                match msg {
-                    AnyMessageLikeEvent::RoomEncrypted(msg) => {
-                        info.insert_encrypted(msg);
-                    },
-                    AnyMessageLikeEvent::RoomMessage(msg) => {
-                        info.insert_with_preview(
-                            room_id.clone(),
-                            store.clone(),
-                            *picker,
-                            msg,
-                            settings,
-                            client.media(),
-                        );
-                    },
-                    AnyMessageLikeEvent::Reaction(ev) => {
-                        info.insert_reaction(ev);
-                    },
-                    _ => continue,
-                }
+                let mut locked = store.lock().await;
+                locked.application.rooms.get_or_default(room_id.clone()).purge = Some(progress);
             }
 
-            info.fetch_id = fetch_id.map_or(RoomFetchStatus::Done, RoomFetchStatus::HaveMore);
-        },
-        Err(e) => {
-            warn!(room_id = room_id.as_str(), err = e.to_string(), "Failed to load older messages");
-
-            // Wait and try again.
-            locked.application.need_load.insert(room_id, Need::MESSAGES);
+            None
         },
-    }
-}
-
-async fn load_older(client: &Client, store: &AsyncProgramStore) -> usize {
-    // This is an arbitrary limit on how much work we do in parallel to avoid
-    // spawning too many tasks at startup and overwhelming the client. We
-    // should normally only surpass this limit at startup when doing an initial.
-    // fetch for each room.
-    const LIMIT: usize = 15;
+        Err(e) => Some(e),
+    };
 
-    // Plans are run in parallel. Any room *may* have several plans.
-    let plans = load_plans(store).await;
-    let permits = Semaphore::new(LIMIT);
+    let mut locked = store.lock().await;
+    let title = locked.application.get_room_title(&room_id);
+    locked.application.rooms.get_or_default(room_id.clone()).purge = None;
 
-    plans
-        .into_iter()
-        .map(|plan| run_plan(client, store, plan, &permits))
-        .collect::<FuturesUnordered<_>>()
-        .count()
-        .await
-}
+    let mut msg = format!(
+        "Redacted {} of {} messages from {user_id} in {title}",
+        progress.redacted, progress.found
+    );
 
-async fn members_load(client: &Client, room_id: &RoomId) -> IambResult<Vec<RoomMember>> {
-    if let Some(room) = client.get_room(room_id) {
-        Ok(room
-            .members_no_sync(RoomMemberships::all())
-            .await
-            .map_err(IambError::from)?)
-    } else {
-        Err(IambError::UnknownRoom(room_id.to_owned()).into())
+    if progress.failed > 0 {
+        msg.push_str(&format!(" ({} failed)", progress.failed));
     }
-}
-
-fn members_insert(
-    room_id: OwnedRoomId,
-    res: IambResult<Vec<RoomMember>>,
-    store: &mut ProgramStore,
-) {
-    if let Ok(members) = res {
-        let ChatStore { rooms, .. } = &mut store.application;
-        let info = rooms.get_or_default(room_id);
 
-        for member in members {
-            let user_id = member.user_id();
-            let display_name =
-                member.display_name().map_or(user_id.to_string(), |str| str.to_string());
-            info.display_names.insert(user_id.to_owned(), display_name);
-        }
+    if let Some(e) = scan_error {
+        msg.push_str(&format!("; stopped looking for messages: {e}"));
     }
-    // else ???
-}
 
-async fn load_older_forever(client: &Client, store: &AsyncProgramStore) {
-    // Load any pending older messages or members every 2 seconds.
-    let mut interval = tokio::time::interval(Duration::from_secs(2));
+    locked.application.notices.push(msg.into());
+}
 
-    loop {
-        interval.tick().await;
-        load_older(client, store).await;
-    }
+/// Whether a room has been upgraded, and we've already joined the room that replaced it.
+///
+/// These rooms get hidden from the room lists, so that the old and new rooms show up as one.
+fn is_replaced(client: &Client, room: &MatrixRoom) -> bool {
+    room.tombstone()
+        .and_then(|tombstone| client.get_room(&tombstone.replacement_room))
+        .is_some_and(|successor| successor.state() == MatrixRoomState::Joined)
 }
 
 async fn refresh_rooms(client: &Client, store: &AsyncProgramStore) {
     let mut names = vec![];
+    let mut tombstones = vec![];
 
     let mut spaces = vec![];
     let mut rooms = vec![];
@@ -461,6 +1591,14 @@ async fn refresh_rooms(client: &Client, store: &AsyncProgramStore) {
 
         names.push((room.room_id().to_owned(), name));
 
+        if let Some(tombstone) = room.tombstone() {
+            tombstones.push((room.room_id().to_owned(), tombstone));
+        }
+
+        if is_replaced(client, &room) {
+            continue;
+        }
+
         if is_direct(&room).await {
             dms.push(Arc::new((room, tags)));
         } else if room.is_space() {
@@ -478,6 +1616,89 @@ async fn refresh_rooms(client: &Client, store: &AsyncProgramStore) {
     for (room_id, name) in names {
         locked.application.set_room_name(&room_id, &name);
     }
+
+    for (room_id, tombstone) in tombstones {
+        locked.application.get_room_info(room_id).tombstone = Some(tombstone);
+    }
+}
+
+async fn watch_identities(room: MatrixRoom, store: AsyncProgramStore) {
+    let changes = match room.subscribe_to_identity_status_changes().await {
+        Ok(changes) => changes,
+        Err(e) => {
+            let room_id = room.room_id().as_str();
+            warn!(room_id, err = e.to_string(), "Failed to watch identity changes");
+            return;
+        },
+    };
+    let mut changes = Box::pin(changes);
+
+    while let Some(changes) = changes.next().await {
+        let mut locked = store.lock().await;
+        let info = locked.application.get_room_info(room.room_id().to_owned());
+
+        for change in changes {
+            if let IdentityState::VerificationViolation = change.changed_to {
+                info.identity_violations.insert(change.user_id);
+            } else {
+                info.identity_violations.remove(&change.user_id);
+            }
+        }
+    }
+}
+
+/// Retry decrypting messages when their keys are downloaded from the key backup, which the SDK
+/// does by itself after failing to decrypt a message.
+async fn watch_backup_keys(room: MatrixRoom, client: Client, store: AsyncProgramStore) {
+    let room_id = room.room_id();
+    let mut keys = Box::pin(client.encryption().backups().room_keys_for_room_stream(room_id));
+
+    while let Some(keys) = keys.next().await {
+        let Ok(keys) = keys else {
+            // Some notifications were missed, so retry everything in the room.
+            retry_decryption(&client, &store, room_id, None).await;
+            continue;
+        };
+
+        for session_id in keys.into_values().flatten() {
+            retry_decryption(&client, &store, room_id, Some(&session_id)).await;
+        }
+    }
+}
+
+/// Watch for identity changes and backup keys in each encrypted room that we're in, and stop
+/// watching rooms after we leave them.
+async fn watch_encrypted_rooms_forever(client: &Client, store: &AsyncProgramStore) {
+    let mut interval = tokio::time::interval(Duration::from_secs(5));
+    let mut watched = HashMap::<OwnedRoomId, [JoinHandle<()>; 2]>::default();
+
+    loop {
+        interval.tick().await;
+
+        let joined = client.joined_rooms();
+
+        watched.retain(|room_id, handles| {
+            let keep = joined.iter().any(|room| room.room_id() == room_id);
+
+            if !keep {
+                handles.iter().for_each(JoinHandle::abort);
+            }
+
+            keep
+        });
+
+        for room in joined {
+            if watched.contains_key(room.room_id()) || !room.is_encrypted().await.unwrap_or(false) {
+                continue;
+            }
+
+            let handles = [
+                tokio::spawn(watch_identities(room.clone(), store.clone())),
+                tokio::spawn(watch_backup_keys(room.clone(), client.clone(), store.clone())),
+            ];
+            watched.insert(room.room_id().to_owned(), handles);
+        }
+    }
 }
 
 async fn refresh_rooms_forever(client: &Client, store: &AsyncProgramStore) {
@@ -610,14 +1831,17 @@ pub enum WorkerTask {
     Init(AsyncProgramStore, ClientReply<()>),
     Login(LoginStyle, ClientReply<IambResult<EditInfo>>),
     Logout(String, ClientReply<IambResult<EditInfo>>),
+    FetchSource(OwnedRoomId, OwnedEventId),
     GetInviter(MatrixRoom, ClientReply<IambResult<Option<RoomMember>>>),
     GetRoom(OwnedRoomId, ClientReply<IambResult<FetchedRoom>>),
     JoinRoom(String, ClientReply<IambResult<OwnedRoomId>>),
     Members(OwnedRoomId, ClientReply<IambResult<Vec<RoomMember>>>),
-    SpaceMembers(OwnedRoomId, ClientReply<IambResult<Vec<OwnedRoomId>>>),
+    Purge(OwnedRoomId, OwnedUserId, PurgeLimit, Option<String>),
+    RefreshDevices,
     TypingNotice(OwnedRoomId),
     Verify(VerifyAction, SasVerification, ClientReply<IambResult<EditInfo>>),
     VerifyRequest(OwnedUserId, ClientReply<IambResult<EditInfo>>),
+    VerifyDevice(OwnedDeviceId, ClientReply<IambResult<EditInfo>>),
 }
 
 impl Debug for WorkerTask {
@@ -641,6 +1865,12 @@ impl Debug for WorkerTask {
             WorkerTask::GetInviter(invite, _) => {
                 f.debug_tuple("WorkerTask::GetInviter").field(invite).finish()
             },
+            WorkerTask::FetchSource(room_id, event_id) => {
+                f.debug_tuple("WorkerTask::FetchSource")
+                    .field(room_id)
+                    .field(event_id)
+                    .finish()
+            },
             WorkerTask::GetRoom(room_id, _) => {
                 f.debug_tuple("WorkerTask::GetRoom")
                     .field(room_id)
@@ -659,12 +1889,15 @@ impl Debug for WorkerTask {
                     .field(&format_args!("_"))
                     .finish()
             },
-            WorkerTask::SpaceMembers(room_id, _) => {
-                f.debug_tuple("WorkerTask::SpaceMembers")
+            WorkerTask::Purge(room_id, user_id, limit, reason) => {
+                f.debug_tuple("WorkerTask::Purge")
                     .field(room_id)
-                    .field(&format_args!("_"))
+                    .field(user_id)
+                    .field(limit)
+                    .field(reason)
                     .finish()
             },
+            WorkerTask::RefreshDevices => f.debug_tuple("WorkerTask::RefreshDevices").finish(),
             WorkerTask::TypingNotice(room_id) => {
                 f.debug_tuple("WorkerTask::TypingNotice").field(room_id).finish()
             },
@@ -681,6 +1914,12 @@ impl Debug for WorkerTask {
                     .field(&format_args!("_"))
                     .finish()
             },
+            WorkerTask::VerifyDevice(device_id, _) => {
+                f.debug_tuple("WorkerTask::VerifyDevice")
+                    .field(device_id)
+                    .field(&format_args!("_"))
+                    .finish()
+            },
         }
     }
 }
@@ -799,16 +2038,26 @@ impl Requester {
         return response.recv();
     }
 
-    pub fn space_members(&self, space: OwnedRoomId) -> IambResult<Vec<OwnedRoomId>> {
-        let (reply, response) = oneshot();
+    pub fn typing_notice(&self, room_id: OwnedRoomId) {
+        self.tx.send(WorkerTask::TypingNotice(room_id)).unwrap();
+    }
 
-        self.tx.send(WorkerTask::SpaceMembers(space, reply)).unwrap();
+    pub fn purge(
+        &self,
+        room_id: OwnedRoomId,
+        user_id: OwnedUserId,
+        limit: PurgeLimit,
+        reason: Option<String>,
+    ) {
+        self.tx.send(WorkerTask::Purge(room_id, user_id, limit, reason)).unwrap();
+    }
 
-        return response.recv();
+    pub fn refresh_devices(&self) {
+        self.tx.send(WorkerTask::RefreshDevices).unwrap();
     }
 
-    pub fn typing_notice(&self, room_id: OwnedRoomId) {
-        self.tx.send(WorkerTask::TypingNotice(room_id)).unwrap();
+    pub fn fetch_source(&self, room_id: OwnedRoomId, event_id: OwnedEventId) {
+        self.tx.send(WorkerTask::FetchSource(room_id, event_id)).unwrap();
     }
 
     pub fn verify(&self, act: VerifyAction, sas: SasVerification) -> IambResult<EditInfo> {
@@ -826,12 +2075,21 @@ impl Requester {
 
         return response.recv();
     }
+
+    pub fn verify_device(&self, device_id: OwnedDeviceId) -> IambResult<EditInfo> {
+        let (reply, response) = oneshot();
+
+        self.tx.send(WorkerTask::VerifyDevice(device_id, reply)).unwrap();
+
+        return response.recv();
+    }
 }
 
 pub struct ClientWorker {
     initialized: bool,
     settings: ApplicationSettings,
     client: Client,
+    store: Option<AsyncProgramStore>,
     load_handle: Option<JoinHandle<()>>,
     sync_handle: Option<JoinHandle<()>>,
 }
@@ -844,6 +2102,7 @@ impl ClientWorker {
             initialized: false,
             settings,
             client: client.clone(),
+            store: None,
             load_handle: None,
             sync_handle: None,
         };
@@ -883,6 +2142,10 @@ impl ClientWorker {
                 assert!(self.initialized);
                 reply.send(self.join_room(room_id).await);
             },
+            WorkerTask::FetchSource(room_id, event_id) => {
+                assert!(self.initialized);
+                self.fetch_source(room_id, event_id);
+            },
             WorkerTask::GetInviter(invited, reply) => {
                 assert!(self.initialized);
                 reply.send(self.get_inviter(invited).await);
@@ -903,9 +2166,13 @@ impl ClientWorker {
                 assert!(self.initialized);
                 reply.send(self.members(room_id).await);
             },
-            WorkerTask::SpaceMembers(space, reply) => {
+            WorkerTask::Purge(room_id, user_id, limit, reason) => {
+                assert!(self.initialized);
+                self.purge(room_id, user_id, limit, reason);
+            },
+            WorkerTask::RefreshDevices => {
                 assert!(self.initialized);
-                reply.send(self.space_members(space).await);
+                self.refresh_devices();
             },
             WorkerTask::TypingNotice(room_id) => {
                 assert!(self.initialized);
@@ -919,11 +2186,16 @@ impl ClientWorker {
                 assert!(self.initialized);
                 reply.send(self.verify_request(user_id).await);
             },
+            WorkerTask::VerifyDevice(device_id, reply) => {
+                assert!(self.initialized);
+                reply.send(self.verify_device(device_id).await);
+            },
         }
     }
 
     async fn init(&mut self, store: AsyncProgramStore) {
         self.client.add_event_handler_context(store.clone());
+        self.store = Some(store.clone());
 
         let _ = self.client.add_event_handler(
             |ev: SyncTypingEvent, room: MatrixRoom, store: Ctx<AsyncProgramStore>| {
@@ -936,6 +2208,7 @@ impl ClientWorker {
                         .user_ids
                         .into_iter()
                         .filter(|u| u != &locked.application.settings.profile.user_id)
+                        .filter(|u| !locked.application.is_ignored(u))
                         .collect();
 
                     locked.application.get_room_info(room_id).set_typing(users);
@@ -943,6 +2216,16 @@ impl ClientWorker {
             },
         );
 
+        let _ = self.client.add_event_handler(
+            |ev: GlobalAccountDataEvent<IgnoredUserListEventContent>,
+             store: Ctx<AsyncProgramStore>| {
+                async move {
+                    let mut locked = store.lock().await;
+                    locked.application.ignored = ev.content.ignored_users.into_keys().collect();
+                }
+            },
+        );
+
         let _ =
             self.client
                 .add_event_handler(|ev: PresenceEvent, store: Ctx<AsyncProgramStore>| {
@@ -968,13 +2251,31 @@ impl ClientWorker {
             },
         );
 
+        let _ = self.client.add_event_handler(
+            |ev: SyncStateEvent<RoomTombstoneEventContent>,
+             room: MatrixRoom,
+             store: Ctx<AsyncProgramStore>| {
+                async move {
+                    if let SyncStateEvent::Original(ev) = ev {
+                        let room_id = room.room_id().to_owned();
+                        let mut locked = store.lock().await;
+                        let info = locked.application.get_room_info(room_id);
+                        info.tombstone = Some(ev.content);
+                    }
+                }
+            },
+        );
+
         let _ = self.client.add_event_handler(
             |ev: SyncMessageLikeEvent<RoomMessageEventContent>,
              room: MatrixRoom,
              client: Client,
+             encryption_info: Option<EncryptionInfo>,
              store: Ctx<AsyncProgramStore>| {
                 async move {
                     let room_id = room.room_id();
+                    let encrypted = room.is_encrypted().await.unwrap_or(false);
+                    let encryption = MessageEncryption::new(encrypted, encryption_info.as_ref());
 
                     if let Some(msg) = ev.as_original() {
                         if let MessageType::VerificationRequest(_) = msg.content.msgtype {
@@ -988,44 +2289,82 @@ impl ClientWorker {
                         }
                     }
 
+                    let ev = ev.into_full_event(room_id.to_owned());
+                    insert_sync_message(ev, &room, &client, encryption, &store).await;
+                }
+            },
+        );
+
+        let _ = self.client.add_event_handler(
+            |ev: SyncMessageLikeEvent<StickerEventContent>,
+             room: MatrixRoom,
+             client: Client,
+             encryption_info: Option<EncryptionInfo>,
+             store: Ctx<AsyncProgramStore>| {
+                async move {
+                    let room_id = room.room_id();
+                    let encrypted = room.is_encrypted().await.unwrap_or(false);
+                    let encryption = MessageEncryption::new(encrypted, encryption_info.as_ref());
+
+                    let ev = sticker_to_message(ev.into_full_event(room_id.to_owned()));
+                    insert_sync_message(ev, &room, &client, encryption, &store).await;
+                }
+            },
+        );
+
+        let _ = self.client.add_event_handler(
+            |ev: SyncMessageLikeEvent<ReactionEventContent>,
+             room: MatrixRoom,
+             store: Ctx<AsyncProgramStore>| {
+                async move {
+                    let room_id = room.room_id();
+
                     let mut locked = store.lock().await;
 
+                    if locked.application.is_ignored(ev.sender()) {
+                        return;
+                    }
+
                     let sender = ev.sender().to_owned();
                     let _ = locked.application.presences.get_or_default(sender);
 
-                    let ChatStore { rooms, picker, settings, .. } = &mut locked.application;
-                    let info = rooms.get_or_default(room_id.to_owned());
-
+                    let info = locked.application.get_room_info(room_id.to_owned());
                     update_event_receipts(info, &room, ev.event_id()).await;
-
-                    let full_ev = ev.into_full_event(room_id.to_owned());
-                    info.insert_with_preview(
-                        room_id.to_owned(),
-                        store.clone(),
-                        *picker,
-                        full_ev,
-                        settings,
-                        client.media(),
-                    );
+                    info.insert_reaction(ev.into_full_event(room_id.to_owned()));
                 }
             },
         );
 
         let _ = self.client.add_event_handler(
-            |ev: SyncMessageLikeEvent<ReactionEventContent>,
+            |ev: AnySyncMessageLikeEvent,
              room: MatrixRoom,
+             encryption_info: Option<EncryptionInfo>,
              store: Ctx<AsyncProgramStore>| {
                 async move {
                     let room_id = room.room_id();
+                    let ev = ev.into_full_event(room_id.to_owned());
+
+                    if !is_poll_event(&ev) {
+                        return;
+                    }
+
+                    let encrypted = room.is_encrypted().await.unwrap_or(false);
+                    let encryption = MessageEncryption::new(encrypted, encryption_info.as_ref());
 
                     let mut locked = store.lock().await;
 
+                    if locked.application.is_ignored(ev.sender()) {
+                        return;
+                    }
+
                     let sender = ev.sender().to_owned();
                     let _ = locked.application.presences.get_or_default(sender);
 
+                    let event_id = ev.event_id().to_owned();
                     let info = locked.application.get_room_info(room_id.to_owned());
-                    update_event_receipts(info, &room, ev.event_id()).await;
-                    info.insert_reaction(ev.into_full_event(room_id.to_owned()));
+                    update_event_receipts(info, &room, &event_id).await;
+                    info.insert_poll(ev);
+                    info.set_encryption(&event_id, encryption);
                 }
             },
         );
@@ -1062,6 +2401,11 @@ impl ClientWorker {
                     let room_version = room_info.room_version().unwrap_or(&RoomVersionId::V1);
 
                     let mut locked = store.lock().await;
+
+                    if let Some(redacts) = &ev.redacts {
+                        locked.application.index.remove(redacts);
+                    }
+
                     let info = locked.application.get_room_info(room_id.to_owned());
                     info.redact(ev, room_version);
                 }
@@ -1214,6 +2558,28 @@ impl ClientWorker {
             },
         );
 
+        let _ = self.client.add_event_handler(
+            |ev: ToDeviceRoomKeyEvent, client: Client, store: Ctx<AsyncProgramStore>| {
+                async move {
+                    let room_id = &ev.content.room_id;
+                    let session_id = ev.content.session_id.as_str();
+
+                    retry_decryption(&client, &store, room_id, Some(session_id)).await;
+                }
+            },
+        );
+
+        let _ = self.client.add_event_handler(
+            |ev: ToDeviceForwardedRoomKeyEvent, client: Client, store: Ctx<AsyncProgramStore>| {
+                async move {
+                    let room_id = &ev.content.room_id;
+                    let session_id = ev.content.session_id.as_str();
+
+                    retry_decryption(&client, &store, room_id, Some(session_id)).await;
+                }
+            },
+        );
+
         self.load_handle = tokio::spawn({
             let client = self.client.clone();
             let settings = self.settings.clone();
@@ -1223,11 +2589,19 @@ impl ClientWorker {
                     tokio::time::sleep(Duration::from_millis(100)).await;
                 }
 
+                match ignored_users(&client).await {
+                    Ok(ignored) => store.lock().await.application.ignored = ignored,
+                    Err(e) => warn!(err = e.to_string(), "Failed to load ignored users"),
+                }
+
                 let load = load_older_forever(&client, &store);
                 let rcpt = send_receipts_forever(&client, &store);
                 let room = refresh_rooms_forever(&client, &store);
                 let notifications = register_notifications(&client, &settings, &store);
-                let ((), (), (), ()) = tokio::join!(load, rcpt, room, notifications);
+                let away = auto_away_forever(&client, &settings, &store);
+                let encrypted = watch_encrypted_rooms_forever(&client, &store);
+                let ((), (), (), (), (), ()) =
+                    tokio::join!(load, rcpt, room, notifications, away, encrypted);
             }
         })
         .into();
@@ -1277,11 +2651,28 @@ impl ClientWorker {
             },
         }
 
+        let store = self.store.clone().expect("worker should be initialized before login");
+
         self.sync_handle = tokio::spawn(async move {
             loop {
-                let settings = SyncSettings::default();
+                // Restart syncing whenever our presence changes, so that we report the new one.
+                let presence = store.lock().await.application.presence.clone();
+                let settings = SyncSettings::default().set_presence(presence.clone());
+
+                let _ = client
+                    .sync_with_callback(settings, |_| {
+                        let store = store.clone();
+                        let presence = presence.clone();
 
-                let _ = client.sync(settings).await;
+                        async move {
+                            if store.lock().await.application.presence == presence {
+                                LoopCtrl::Continue
+                            } else {
+                                LoopCtrl::Break
+                            }
+                        }
+                    })
+                    .await;
             }
         })
         .into();
@@ -1328,7 +2719,7 @@ impl ClientWorker {
         let rt = CreateRoomType::Direct(user.clone());
         let flags = CreateRoomFlags::ENCRYPTED;
 
-        create_room(&self.client, None, rt, flags).await.map_err(|e| {
+        create_room(&self.client, None, rt, flags, None).await.map_err(|e| {
             error!(
                 user_id = user.as_str(),
                 err = e.to_string(),
@@ -1380,22 +2771,71 @@ impl ClientWorker {
 
     async fn members(&mut self, room_id: OwnedRoomId) -> IambResult<Vec<RoomMember>> {
         if let Some(room) = self.client.get_room(room_id.as_ref()) {
-            Ok(room.members(RoomMemberships::ACTIVE).await.map_err(IambError::from)?)
+            let mut memberships = RoomMemberships::ACTIVE;
+
+            // Users who can let others in should see who's knocking.
+            if let Some(user_id) = self.client.user_id() {
+                if room.can_user_invite(user_id).await.unwrap_or_default() {
+                    memberships |= RoomMemberships::KNOCK;
+                }
+            }
+
+            Ok(room.members(memberships).await.map_err(IambError::from)?)
         } else {
             Err(IambError::UnknownRoom(room_id).into())
         }
     }
 
-    async fn space_members(&mut self, space: OwnedRoomId) -> IambResult<Vec<OwnedRoomId>> {
-        let mut req = SpaceHierarchyRequest::new(space);
-        req.limit = Some(1000u32.into());
-        req.max_depth = Some(1u32.into());
+    fn purge(
+        &mut self,
+        room_id: OwnedRoomId,
+        user_id: OwnedUserId,
+        limit: PurgeLimit,
+        reason: Option<String>,
+    ) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+
+        tokio::spawn(purge(self.client.clone(), store, room_id, user_id, limit, reason));
+    }
+
+    fn refresh_devices(&mut self) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
+
+        let client = self.client.clone();
+        let user_id = self.settings.profile.user_id.clone();
+
+        tokio::spawn(async move {
+            match list_devices(&client, &user_id).await {
+                Ok(devices) => store.lock().await.application.devices = devices,
+                Err(e) => warn!(err = e.to_string(), "Failed to fetch devices"),
+            }
+        });
+    }
 
-        let resp = self.client.send(req, None).await.map_err(IambError::from)?;
+    fn fetch_source(&mut self, room_id: OwnedRoomId, event_id: OwnedEventId) {
+        let Some(store) = self.store.clone() else {
+            return;
+        };
 
-        let rooms = resp.rooms.into_iter().map(|chunk| chunk.room_id).collect();
+        let client = self.client.clone();
 
-        Ok(rooms)
+        tokio::spawn(async move {
+            match get_event_source(&client, &room_id, &event_id).await {
+                Ok(source) => {
+                    let id = IambBufferId::Source(room_id, event_id);
+                    let buffer = store.lock().await.load_buffer(id);
+
+                    if let Ok(mut buf) = buffer.write() {
+                        buf.set_text(source);
+                    };
+                },
+                Err(e) => warn!(err = e.to_string(), "Failed to fetch event source"),
+            }
+        });
     }
 
     async fn typing_notice(&mut self, room_id: OwnedRoomId) {
@@ -1470,4 +2910,26 @@ impl ClientWorker {
             },
         }
     }
+
+    async fn verify_device(&self, device_id: OwnedDeviceId) -> IambResult<EditInfo> {
+        let enc = self.client.encryption();
+        let user_id = self.settings.profile.user_id.as_ref();
+
+        match enc.get_device(user_id, &device_id).await.map_err(IambError::from)? {
+            Some(device) => {
+                let methods = vec![VerificationMethod::SasV1];
+                let request = device.request_verification_with_methods(methods);
+                let _req = request.await.map_err(IambError::from)?;
+                let info = format!("Sent verification request to device {device_id}");
+
+                Ok(Some(InfoMessage::from(info)))
+            },
+            None => {
+                let msg = format!("Could not find encryption information for device {device_id}");
+                let err = UIError::Failure(msg);
+
+                Err(err)
+            },
+        }
+    }
 }